@@ -0,0 +1,348 @@
+use super::merkle::verify_chunk;
+use super::response::check_response;
+use super::retry::{Retries, RetryPolicy};
+use super::Downloader;
+use crate::errors::ClientError;
+use crate::transaction::bundle::{BundleItem, BundleStream};
+use base64::{decode_config, URL_SAFE_NO_PAD};
+use bytes::Bytes;
+use reqwest::{Client, Url};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use tokio_stream::StreamExt;
+
+/// Size and offset of the last byte of a transaction in the weave, as
+/// returned by `/tx/{id}/offset`.
+#[derive(Debug, Deserialize)]
+struct TransactionOffset {
+    size: String,
+    offset: String,
+}
+
+/// Fields of the transaction header from `/tx/{id}` that commit to its data.
+#[derive(Debug, Deserialize)]
+struct TransactionHeader {
+    data_root: String,
+    data_size: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Chunk {
+    chunk: String,
+    data_path: String,
+}
+
+/// Data of a transaction as committed to by its header.
+struct DataRange {
+    /// Absolute offset of the first byte of the data.
+    start: u64,
+    size: u64,
+    data_root: Vec<u8>,
+}
+
+/// Downloads the data of a transaction chunk by chunk through the `/chunk`
+/// API, for gateways that refuse or truncate large bundles on `/tx/{id}/data`.
+///
+/// Each chunk is checked against the `data_root` of the transaction header
+/// with its Merkle proof before being parsed. Each request is retried on its
+/// own following the [`RetryPolicy`].
+#[derive(Clone)]
+pub struct ChunkDownloader {
+    url: Url,
+    client: Client,
+    retry_policy: RetryPolicy,
+}
+
+impl ChunkDownloader {
+    pub fn new(url: String) -> Result<Self, ClientError> {
+        let url = Url::parse(&url).map_err(|e| ClientError::ParseUrl(e.to_string()))?;
+        Ok(Self {
+            url,
+            client: Client::new(),
+            retry_policy: RetryPolicy::default(),
+        })
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    async fn data_range(&self, transaction_id: &str) -> Result<DataRange, ClientError> {
+        let offset: TransactionOffset = self
+            .get_json(&format!("tx/{}/offset", transaction_id))
+            .await?;
+        let header: TransactionHeader = self.get_json(&format!("tx/{}", transaction_id)).await?;
+        let parse = |value: &str| {
+            value.parse::<u64>().map_err(|_| {
+                ClientError::InvalidResponse(format!(
+                    "Invalid offset {} for {}",
+                    value, transaction_id
+                ))
+            })
+        };
+        let (size, end) = (parse(&offset.size)?, parse(&offset.offset)?);
+        let data_size = parse(&header.data_size)?;
+        let start = end
+            .checked_add(1)
+            .and_then(|next| next.checked_sub(size))
+            .filter(|_| size > 0 && data_size == size)
+            .ok_or_else(|| {
+                ClientError::InvalidResponse(format!(
+                    "Invalid size {} at offset {} for {}",
+                    size, end, transaction_id
+                ))
+            })?;
+        Ok(DataRange {
+            start,
+            size,
+            data_root: decode_config(header.data_root, URL_SAFE_NO_PAD)?,
+        })
+    }
+
+    /// Chunk holding the byte at `offset` within the data, from its start once
+    /// checked against the data root.
+    async fn chunk(&self, range: &DataRange, offset: u64) -> Result<Bytes, ClientError> {
+        let chunk: Chunk = self
+            .get_json(&format!("chunk/{}", range.start + offset))
+            .await?;
+        let data_path = decode_config(chunk.data_path, URL_SAFE_NO_PAD)?;
+        let chunk = Bytes::from(decode_config(chunk.chunk, URL_SAFE_NO_PAD)?);
+        let bounds = verify_chunk(&range.data_root, offset, range.size, &data_path, &chunk)
+            .ok_or(ClientError::InvalidChunkProof(range.start + offset))?;
+        Ok(chunk.slice((offset - bounds.start) as usize..))
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, ClientError> {
+        let url = self
+            .url
+            .join(path)
+            .map_err(|e| ClientError::ParseUrl(e.to_string()))?;
+        let mut retries = Retries::new(&self.retry_policy, format!("GET {}", url));
+        loop {
+            match get_json(&self.client, &url).await {
+                Ok(value) => return Ok(value),
+                Err(e) => retries.retry(e).await?,
+            }
+        }
+    }
+}
+
+async fn get_json<T: DeserializeOwned>(client: &Client, url: &Url) -> Result<T, ClientError> {
+    let response = check_response(client.get(url.clone()).send().await?).await?;
+    Ok(response.json().await?)
+}
+
+#[async_trait::async_trait]
+impl Downloader for ChunkDownloader {
+    async fn download(&self, transaction_id: String) -> Result<BundleStream, ClientError> {
+        let range = self.data_range(&transaction_id).await?;
+        let chunks = futures_util::stream::try_unfold(
+            (self.clone(), range, 0),
+            |(downloader, range, offset)| async move {
+                if offset >= range.size {
+                    return Ok(None);
+                }
+                let chunk = downloader.chunk(&range, offset).await?;
+                let next = offset + chunk.len() as u64;
+                Ok(Some((chunk, (downloader, range, next))))
+            },
+        )
+        .map(|chunk: Result<Bytes, ClientError>| chunk.map_err(std::io::Error::other));
+
+        let bundles = BundleItem::stream_chunks(chunks).await?;
+        Ok(bundles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::merkle::build_tree;
+    use crate::errors::ParseError;
+    use base64::encode_config;
+    use bytes::BytesMut;
+    use serde_json::json;
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// Maximum size of the chunks of the weave.
+    const CHUNK_SIZE: usize = 256 * 1024;
+    /// Offset of the first byte of the fixture bundle in the mock weave.
+    const START: usize = 1_000_000;
+
+    /// Gateway serving the fixture bundle through the chunk API, `tamper`
+    /// modifying the data of the served chunks.
+    async fn mock_gateway(
+        data: &[u8],
+        served_chunks: usize,
+        tamper: impl Fn(usize, &mut Vec<u8>),
+    ) -> MockServer {
+        let server = MockServer::start().await;
+        let (data_root, paths) = build_tree(data, CHUNK_SIZE);
+        Mock::given(method("GET"))
+            .and(path("/tx/bundle"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "bundle",
+                "data_root": encode_config(data_root, URL_SAFE_NO_PAD),
+                "data_size": data.len().to_string(),
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/tx/bundle/offset"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "size": data.len().to_string(),
+                "offset": (START + data.len() - 1).to_string(),
+            })))
+            .mount(&server)
+            .await;
+        let chunks = data.chunks(CHUNK_SIZE).zip(paths);
+        for (i, (chunk, data_path)) in chunks.take(served_chunks).enumerate() {
+            let mut chunk = chunk.to_vec();
+            tamper(i, &mut chunk);
+            Mock::given(method("GET"))
+                .and(path(format!("/chunk/{}", START + i * CHUNK_SIZE)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "chunk": encode_config(chunk, URL_SAFE_NO_PAD),
+                    "data_path": encode_config(data_path, URL_SAFE_NO_PAD),
+                    "tx_path": "",
+                })))
+                .up_to_n_times(1)
+                .mount(&server)
+                .await;
+        }
+        server
+    }
+
+    #[tokio::test]
+    async fn test_download_chunks() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let server = mock_gateway(&data, usize::MAX, |_, _| {}).await;
+        let mut expected = BundleItem::stream(BytesMut::from(data.as_slice())).unwrap();
+
+        let downloader = ChunkDownloader::new(server.uri()).unwrap();
+        let mut stream = downloader.download("bundle".to_string()).await.unwrap();
+
+        let mut count = 0;
+        while let Some(item) = stream.next().await {
+            assert_eq!(item.unwrap(), expected.next().await.unwrap().unwrap());
+            count += 1;
+        }
+        assert_eq!(count, 71);
+    }
+
+    #[tokio::test]
+    async fn test_download_retries_chunk() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let server = mock_gateway(&data, usize::MAX, |_, _| {}).await;
+        for path_failing in [
+            "/tx/bundle/offset".to_string(),
+            format!("/chunk/{}", START + CHUNK_SIZE),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(path_failing))
+                .respond_with(ResponseTemplate::new(503))
+                .with_priority(1)
+                .up_to_n_times(1)
+                .mount(&server)
+                .await;
+        }
+        let retry_policy = RetryPolicy::default()
+            .with_max_attempts(2)
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(5));
+
+        let downloader = ChunkDownloader::new(server.uri())
+            .unwrap()
+            .with_retry_policy(retry_policy);
+        let mut stream = downloader.download("bundle".to_string()).await.unwrap();
+
+        let mut count = 0;
+        while let Some(item) = stream.next().await {
+            item.unwrap();
+            count += 1;
+        }
+        assert_eq!(count, 71);
+    }
+
+    #[tokio::test]
+    async fn test_download_invalid_offset() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/tx/bundle"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "bundle",
+                "data_root": "",
+                "data_size": "1",
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/tx/bundle/offset"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "size": "1",
+                "offset": u64::MAX.to_string(),
+            })))
+            .mount(&server)
+            .await;
+
+        let downloader = ChunkDownloader::new(server.uri()).unwrap();
+        let result = downloader.download("bundle".to_string()).await;
+
+        assert!(matches!(result, Err(ClientError::InvalidResponse(_))));
+    }
+
+    #[tokio::test]
+    async fn test_download_missing_chunk() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let server = mock_gateway(&data, 2, |_, _| {}).await;
+
+        let downloader = ChunkDownloader::new(server.uri()).unwrap();
+        let mut stream = downloader.download("bundle".to_string()).await.unwrap();
+
+        let mut results = vec![];
+        while let Some(item) = stream.next().await {
+            results.push(item);
+        }
+        // Items in the served chunks are still parsed
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results.last(),
+            Some(Err(ParseError::Download(e))) if matches!(e.client_error(), ClientError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_download_tampered_chunk() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let server = mock_gateway(&data, usize::MAX, |i, chunk| {
+            if i == 1 {
+                chunk[100] ^= 1;
+            }
+        })
+        .await;
+
+        let downloader = ChunkDownloader::new(server.uri()).unwrap();
+        let mut stream = downloader.download("bundle".to_string()).await.unwrap();
+
+        let mut results = vec![];
+        while let Some(item) = stream.next().await {
+            results.push(item);
+        }
+        let offset = (START + CHUNK_SIZE) as u64;
+        assert!(matches!(
+            results.last(),
+            Some(Err(ParseError::Download(e)))
+                if matches!(e.client_error(), ClientError::InvalidChunkProof(o) if *o == offset)
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::errors::ClientError;
+use base64::{decode_config, URL_SAFE_NO_PAD};
+use bytes::{Bytes, BytesMut};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio_stream::Stream;
+
+/// Decodes a stream of base64url chunks into a stream of raw bytes.
+///
+/// Chunks are not required to be aligned to 4-character quanta: incomplete
+/// quanta are kept until the next chunk arrives, and whatever is left at the
+/// end of the inner stream is decoded as the unpadded tail.
+pub struct Base64Decoder<S> {
+    inner: Pin<Box<S>>,
+    pending: BytesMut,
+    done: bool,
+}
+
+impl<S> Base64Decoder<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner: Box::pin(inner),
+            pending: BytesMut::new(),
+            done: false,
+        }
+    }
+}
+
+impl<S, E> Stream for Base64Decoder<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: Into<ClientError>,
+{
+    type Item = Result<Bytes, ClientError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.as_mut().get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+            match this.inner.as_mut().poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
+                Poll::Ready(Some(Ok(chunk))) => {
+                    this.pending.extend_from_slice(&chunk);
+                    let complete = this.pending.len() / 4 * 4;
+                    if complete == 0 {
+                        continue;
+                    }
+                    let quanta = this.pending.split_to(complete);
+                    return Poll::Ready(Some(decode(&quanta)));
+                }
+                Poll::Ready(None) => {
+                    this.done = true;
+                    if this.pending.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                    let tail = this.pending.split();
+                    return Poll::Ready(Some(decode(&tail)));
+                }
+            }
+        }
+    }
+}
+
+fn decode(data: &[u8]) -> Result<Bytes, ClientError> {
+    Ok(Bytes::from(decode_config(data, URL_SAFE_NO_PAD)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::encode_config;
+    use tokio_stream::StreamExt;
+
+    async fn decode_chunks(encoded: &[u8], chunk_size: usize) -> Result<Vec<u8>, ClientError> {
+        let chunks = encoded
+            .chunks(chunk_size)
+            .map(|c| Ok::<_, ClientError>(Bytes::copy_from_slice(c)))
+            .collect::<Vec<_>>();
+        let mut decoder = Base64Decoder::new(tokio_stream::iter(chunks));
+        let mut output = vec![];
+        while let Some(chunk) = decoder.next().await {
+            output.extend_from_slice(&chunk?);
+        }
+        Ok(output)
+    }
+
+    #[tokio::test]
+    async fn test_decode_split_quanta() {
+        for len in 0..20 {
+            let data = (0..len).map(|i| (i * 37) as u8).collect::<Vec<u8>>();
+            let encoded = encode_config(&data, URL_SAFE_NO_PAD);
+            for chunk_size in 1..9 {
+                let decoded = decode_chunks(encoded.as_bytes(), chunk_size).await.unwrap();
+                assert_eq!(decoded, data);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_bundle() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let encoded = encode_config(&data, URL_SAFE_NO_PAD);
+
+        let decoded = decode_chunks(encoded.as_bytes(), 16 * 1024 + 3)
+            .await
+            .unwrap();
+
+        assert_eq!(decoded, data);
+    }
+
+    #[tokio::test]
+    async fn test_decode_invalid() {
+        let result = decode_chunks(b"QUJD*EVG", 3).await;
+        assert!(matches!(result, Err(ClientError::DecodeBase64Error(_))));
+    }
+}
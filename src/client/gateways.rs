@@ -0,0 +1,376 @@
+use super::Downloader;
+use crate::errors::ClientError;
+use crate::transaction::bundle::BundleStream;
+use futures_util::future::{select, Either};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Consecutive failures after which a gateway is tried last.
+pub const DEFAULT_MAX_FAILURES: u32 = 3;
+/// Time a failing gateway is tried last before getting another chance.
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+/// Attempts per gateway suggested when several are configured, so that
+/// failing over is not delayed by long retries on a single gateway.
+pub const MULTI_GATEWAY_MAX_ATTEMPTS: u32 = 2;
+
+/// Outcome of the last downloads from a gateway.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GatewayHealth {
+    pub consecutive_failures: u32,
+    pub last_failure: Option<Instant>,
+    /// Time taken to start the last successful download.
+    pub last_latency: Option<Duration>,
+}
+
+impl GatewayHealth {
+    fn record_failure(&mut self) {
+        self.consecutive_failures += 1;
+        self.last_failure = Some(Instant::now());
+    }
+
+    fn is_down(&self, max_failures: u32, cooldown: Duration) -> bool {
+        self.consecutive_failures >= max_failures
+            && self.last_failure.is_some_and(|t| t.elapsed() < cooldown)
+    }
+}
+
+struct Gateway<D> {
+    url: String,
+    downloader: D,
+    health: Mutex<GatewayHealth>,
+}
+
+/// Downloads bundles from an ordered list of gateways, falling back on the
+/// next one when a gateway fails to start the download.
+///
+/// Gateways failing repeatedly are tried after the healthy ones until their
+/// cooldown is over. With racing, the first two gateways are asked at once
+/// and the first to answer serves the bundle. Items of the downloaded bundle
+/// record the gateway that served it.
+///
+/// Failures after the download started, reported with
+/// [`Downloader::report_failure`], count towards the health of the gateway
+/// but the bundle is not restarted on another one: the caller gets the error
+/// and a new download prefers the healthy gateways.
+pub struct MultiGatewayDownloader<D> {
+    gateways: Vec<Gateway<D>>,
+    race: bool,
+    max_failures: u32,
+    cooldown: Duration,
+}
+
+impl<D> MultiGatewayDownloader<D>
+where
+    D: Downloader + Sync,
+{
+    /// Gateways in order of preference, each with its URL and downloader.
+    pub fn new(gateways: Vec<(String, D)>) -> Self {
+        Self {
+            gateways: gateways
+                .into_iter()
+                .map(|(url, downloader)| Gateway {
+                    url,
+                    downloader,
+                    health: Mutex::new(GatewayHealth::default()),
+                })
+                .collect(),
+            race: false,
+            max_failures: DEFAULT_MAX_FAILURES,
+            cooldown: DEFAULT_COOLDOWN,
+        }
+    }
+
+    pub fn with_racing(mut self, race: bool) -> Self {
+        self.race = race;
+        self
+    }
+
+    pub fn with_health_threshold(mut self, max_failures: u32, cooldown: Duration) -> Self {
+        self.max_failures = max_failures;
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Health of each gateway, in the configured order.
+    pub fn health(&self) -> Vec<(String, GatewayHealth)> {
+        self.gateways
+            .iter()
+            .map(|g| (g.url.clone(), g.health.lock().unwrap().clone()))
+            .collect()
+    }
+
+    /// Healthy gateways first, in the configured order.
+    fn candidates(&self) -> Vec<&Gateway<D>> {
+        let mut candidates = self.gateways.iter().collect::<Vec<_>>();
+        candidates.sort_by_key(|g| {
+            g.health
+                .lock()
+                .unwrap()
+                .is_down(self.max_failures, self.cooldown)
+        });
+        candidates
+    }
+
+    async fn try_gateway(
+        &self,
+        gateway: &Gateway<D>,
+        transaction_id: &str,
+    ) -> Result<BundleStream, ClientError> {
+        let start = Instant::now();
+        let result = gateway
+            .downloader
+            .download(transaction_id.to_string())
+            .await;
+        let mut health = gateway.health.lock().unwrap();
+        match result {
+            Ok(stream) => {
+                health.consecutive_failures = 0;
+                health.last_latency = Some(start.elapsed());
+                Ok(stream.with_gateway(gateway.url.clone()))
+            }
+            Err(e) => {
+                health.record_failure();
+                log::warn!(
+                    "Gateway {} failed to serve {}: {}",
+                    gateway.url,
+                    transaction_id,
+                    e
+                );
+                Err(e)
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<D> Downloader for MultiGatewayDownloader<D>
+where
+    D: Downloader + Send + Sync,
+{
+    async fn download(&self, transaction_id: String) -> Result<BundleStream, ClientError> {
+        let candidates = self.candidates();
+        let mut failures = vec![];
+        let mut remaining = candidates.as_slice();
+        if let [first, second, rest @ ..] = remaining {
+            if self.race {
+                let first_result = Box::pin(self.try_gateway(first, &transaction_id));
+                let second_result = Box::pin(self.try_gateway(second, &transaction_id));
+                // The slower download is cancelled once the other succeeds
+                match select(first_result, second_result).await {
+                    Either::Left((Ok(stream), _)) | Either::Right((Ok(stream), _)) => {
+                        return Ok(stream)
+                    }
+                    Either::Left((Err(e), other)) => {
+                        failures.push((first.url.clone(), e));
+                        match other.await {
+                            Ok(stream) => return Ok(stream),
+                            Err(e) => failures.push((second.url.clone(), e)),
+                        }
+                    }
+                    Either::Right((Err(e), other)) => {
+                        failures.push((second.url.clone(), e));
+                        match other.await {
+                            Ok(stream) => return Ok(stream),
+                            Err(e) => failures.push((first.url.clone(), e)),
+                        }
+                    }
+                }
+                remaining = rest;
+            }
+        }
+        for gateway in remaining {
+            match self.try_gateway(gateway, &transaction_id).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => failures.push((gateway.url.clone(), e)),
+            }
+        }
+        Err(ClientError::AllGatewaysFailed(failures))
+    }
+
+    fn report_failure(&self, stream: &BundleStream) {
+        let Some(gateway) = self
+            .gateways
+            .iter()
+            .find(|g| Some(g.url.as_str()) == stream.gateway())
+        else {
+            return;
+        };
+        log::warn!("Gateway {} failed while streaming a bundle", gateway.url);
+        gateway.health.lock().unwrap().record_failure();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::bundle::BundleItem;
+    use bytes::BytesMut;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio_stream::StreamExt;
+
+    struct FakeGateway {
+        data: Option<Vec<u8>>,
+        delay: Duration,
+        calls: AtomicUsize,
+    }
+
+    impl FakeGateway {
+        fn new(data: Option<&[u8]>, delay: Duration) -> Self {
+            Self {
+                data: data.map(<[u8]>::to_vec),
+                delay,
+                calls: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Downloader for FakeGateway {
+        async fn download(&self, _transaction_id: String) -> Result<BundleStream, ClientError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            match &self.data {
+                Some(data) => Ok(BundleItem::stream(BytesMut::from(data.as_slice()))?),
+                None => Err(ClientError::InvalidResponse("unavailable".to_string())),
+            }
+        }
+    }
+
+    fn downloader(gateways: Vec<(&str, FakeGateway)>) -> MultiGatewayDownloader<FakeGateway> {
+        MultiGatewayDownloader::new(
+            gateways
+                .into_iter()
+                .map(|(url, gateway)| (url.to_string(), gateway))
+                .collect(),
+        )
+    }
+
+    fn calls(downloader: &MultiGatewayDownloader<FakeGateway>) -> Vec<usize> {
+        downloader
+            .gateways
+            .iter()
+            .map(|g| g.downloader.calls.load(Ordering::SeqCst))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let downloader = downloader(vec![
+            ("down", FakeGateway::new(None, Duration::ZERO)),
+            ("up", FakeGateway::new(Some(&data), Duration::ZERO)),
+        ]);
+
+        let mut stream = downloader.download("bundle".to_string()).await.unwrap();
+
+        assert_eq!(stream.gateway(), Some("up"));
+        let item = stream.next().await.unwrap().unwrap();
+        assert_eq!(item.gateway(), Some("up"));
+        let health = downloader.health();
+        assert_eq!(health[0].1.consecutive_failures, 1);
+        assert!(health[1].1.last_latency.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_report_failure() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let downloader = downloader(vec![
+            ("first", FakeGateway::new(Some(&data), Duration::ZERO)),
+            ("second", FakeGateway::new(Some(&data), Duration::ZERO)),
+        ])
+        .with_health_threshold(1, Duration::from_secs(60));
+
+        let stream = downloader.download("bundle".to_string()).await.unwrap();
+        assert_eq!(stream.gateway(), Some("first"));
+        downloader.report_failure(&stream);
+
+        let health = downloader.health();
+        assert_eq!(health[0].1.consecutive_failures, 1);
+        assert!(health[0].1.last_failure.is_some());
+        let stream = downloader.download("bundle".to_string()).await.unwrap();
+        assert_eq!(stream.gateway(), Some("second"));
+    }
+
+    #[tokio::test]
+    async fn test_unhealthy_gateway_tried_last() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let downloader = downloader(vec![
+            ("down", FakeGateway::new(None, Duration::ZERO)),
+            ("up", FakeGateway::new(Some(&data), Duration::ZERO)),
+        ])
+        .with_health_threshold(1, Duration::from_secs(60));
+
+        for _ in 0..3 {
+            let stream = downloader.download("bundle".to_string()).await.unwrap();
+            assert_eq!(stream.gateway(), Some("up"));
+        }
+
+        assert_eq!(calls(&downloader), vec![1, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_race() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let downloader = downloader(vec![
+            (
+                "slow",
+                FakeGateway::new(Some(&data), Duration::from_secs(5)),
+            ),
+            ("fast", FakeGateway::new(Some(&data), Duration::ZERO)),
+            ("spare", FakeGateway::new(Some(&data), Duration::ZERO)),
+        ])
+        .with_racing(true);
+
+        let stream = downloader.download("bundle".to_string()).await.unwrap();
+
+        assert_eq!(stream.gateway(), Some("fast"));
+        assert_eq!(calls(&downloader), vec![1, 1, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_race_failure() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let downloader = downloader(vec![
+            (
+                "slow",
+                FakeGateway::new(Some(&data), Duration::from_millis(50)),
+            ),
+            ("down", FakeGateway::new(None, Duration::ZERO)),
+        ])
+        .with_racing(true);
+
+        let stream = downloader.download("bundle".to_string()).await.unwrap();
+
+        assert_eq!(stream.gateway(), Some("slow"));
+        assert_eq!(downloader.health()[1].1.consecutive_failures, 1);
+    }
+
+    #[tokio::test]
+    async fn test_all_gateways_failed() {
+        let downloader = downloader(vec![
+            ("first", FakeGateway::new(None, Duration::ZERO)),
+            ("second", FakeGateway::new(None, Duration::ZERO)),
+        ]);
+
+        let result = downloader.download("bundle".to_string()).await;
+
+        let Err(ClientError::AllGatewaysFailed(failures)) = result else {
+            panic!("Expected all gateways to fail");
+        };
+        let urls = failures
+            .iter()
+            .map(|(url, _)| url.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(urls, vec!["first", "second"]);
+    }
+}
@@ -1,37 +1,380 @@
+use super::decoder::Base64Decoder;
+use super::response::check_response;
+use super::retry::{Retries, RetryPolicy};
 use crate::errors::ClientError;
 use crate::transaction::bundle::{BundleItem, BundleStream};
-use base64::{decode_config, URL_SAFE_NO_PAD};
-use bytes::BytesMut;
-use reqwest::{Client, Url};
+use bytes::Bytes;
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::{Client, StatusCode, Url};
+use std::pin::Pin;
+use tokio_stream::{Stream, StreamExt};
 
 use super::Downloader;
 
+type BodyStream = Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>;
+
+/// Downloads bundles from `/tx/{id}/data`.
+///
+/// Transient failures, like timeouts, dropped connections or the statuses of
+/// the [`RetryPolicy`], are retried with backoff, and an interrupted transfer
+/// is resumed from the last received byte with a `Range` header. Missing
+/// transactions, pending ones and error pages are reported without retrying.
 pub struct HttpDownloader {
     url: Url,
+    client: Client,
+    retry_policy: RetryPolicy,
 }
 
 impl HttpDownloader {
     pub fn new(url: String) -> Result<Self, ClientError> {
         let url = Url::parse(&url).map_err(|e| ClientError::ParseUrl(e.to_string()))?;
-        Ok(Self { url })
+        Ok(Self {
+            url,
+            client: Client::new(),
+            retry_policy: RetryPolicy::default(),
+        })
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
     }
 }
 
 #[async_trait::async_trait]
 impl Downloader for HttpDownloader {
     async fn download(&self, transaction_id: String) -> Result<BundleStream, ClientError> {
-        let client = Client::new();
         let url = self
             .url
             .join(format!("tx/{}/data", transaction_id).as_str())
             .map_err(|e| ClientError::ParseUrl(e.to_string()))?;
 
-        let response = client.get(url).send().await?;
-        let output = response.bytes().await?;
-        let output = decode_config(output, URL_SAFE_NO_PAD)?;
-        let data = BytesMut::from(output.as_slice());
+        let mut body = ResumableBody::new(self.client.clone(), url, self.retry_policy.clone());
+        body.connect().await?;
+        let chunks = futures_util::stream::try_unfold(body, |mut body| async move {
+            let chunk = body.next_chunk().await?;
+            Ok::<_, ClientError>(chunk.map(|chunk| (chunk, body)))
+        });
+        let data = Base64Decoder::new(chunks).map(|chunk| chunk.map_err(std::io::Error::other));
 
-        let bundles = BundleItem::stream(data)?;
+        let bundles = BundleItem::stream_chunks(data).await?;
         Ok(bundles)
     }
 }
+
+/// Body of a `GET` request that is requested again from the last received
+/// byte with a `Range` header when the transfer is interrupted.
+struct ResumableBody {
+    client: Client,
+    url: Url,
+    policy: RetryPolicy,
+    /// Bytes of the body received so far.
+    received: u64,
+    /// Bytes to drop from the current response, when the server ignored the
+    /// requested range and sends the body from the start.
+    skip: u64,
+    body: Option<BodyStream>,
+    /// First chunk, received while connecting.
+    pending: Option<Bytes>,
+}
+
+impl ResumableBody {
+    fn new(client: Client, url: Url, policy: RetryPolicy) -> Self {
+        Self {
+            client,
+            url,
+            policy,
+            received: 0,
+            skip: 0,
+            body: None,
+            pending: None,
+        }
+    }
+
+    /// Send the request and wait for the first bytes of the body, so that a
+    /// failed request or an empty body is reported before streaming.
+    async fn connect(&mut self) -> Result<(), ClientError> {
+        self.pending = self.next_chunk().await?;
+        Ok(())
+    }
+
+    /// Next chunk of the body, resuming the transfer on retryable errors.
+    /// Attempts are counted from the last chunk received.
+    async fn next_chunk(&mut self) -> Result<Option<Bytes>, ClientError> {
+        if let Some(chunk) = self.pending.take() {
+            return Ok(Some(chunk));
+        }
+        let mut retries = Retries::new(&self.policy, format!("GET {}", self.url));
+        loop {
+            let Some(body) = self.body.as_mut() else {
+                match request(&self.client, &self.url, self.received).await {
+                    Ok((body, skip)) => (self.body, self.skip) = (Some(body), skip),
+                    Err(e) => retries.retry(e).await?,
+                }
+                continue;
+            };
+            match body.next().await {
+                Some(Ok(mut chunk)) => {
+                    let skipped = self.skip.min(chunk.len() as u64);
+                    self.skip -= skipped;
+                    let chunk = chunk.split_off(skipped as usize);
+                    if chunk.is_empty() {
+                        continue;
+                    }
+                    self.received += chunk.len() as u64;
+                    return Ok(Some(chunk));
+                }
+                Some(Err(e)) => {
+                    self.body = None;
+                    retries.retry(e.into()).await?;
+                    log::info!("Resuming GET {} from byte {}", self.url, self.received);
+                }
+                None if self.received == 0 => {
+                    return Err(ClientError::MissingData(self.url.to_string()))
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Request the body from byte `start`, returning it with the number of bytes
+/// to skip when the server answers with the whole body.
+async fn request(client: &Client, url: &Url, start: u64) -> Result<(BodyStream, u64), ClientError> {
+    let mut request = client.get(url.clone());
+    if start > 0 {
+        request = request.header(RANGE, format!("bytes={}-", start));
+    }
+    let response = check_response(request.send().await?).await?;
+    if start == 0 {
+        return Ok((Box::pin(response.bytes_stream()), 0));
+    }
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        log::warn!(
+            "GET {} ignored the range request, skipping {} bytes",
+            url,
+            start
+        );
+        return Ok((Box::pin(response.bytes_stream()), start));
+    }
+    let content_range = response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !content_range.starts_with(&format!("bytes {}-", start)) {
+        return Err(ClientError::InvalidResponse(format!(
+            "Content-Range '{}' does not start at byte {}",
+            content_range, start
+        )));
+    }
+    Ok((Box::pin(response.bytes_stream()), 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ParseError;
+    use base64::{encode_config, URL_SAFE_NO_PAD};
+    use bytes::BytesMut;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn fast_retries(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::default()
+            .with_max_attempts(max_attempts)
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(5))
+    }
+
+    async fn assert_downloads_fixture(stream: &mut BundleStream, data: &[u8]) {
+        let mut expected = BundleItem::stream(BytesMut::from(data)).unwrap();
+        let mut count = 0;
+        while let Some(item) = stream.next().await {
+            assert_eq!(item.unwrap(), expected.next().await.unwrap().unwrap());
+            count += 1;
+        }
+        assert_eq!(count, 71);
+    }
+
+    #[tokio::test]
+    async fn test_retry_unavailable() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/tx/bundle/data"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/tx/bundle/data"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(encode_config(&data, URL_SAFE_NO_PAD)),
+            )
+            .mount(&server)
+            .await;
+
+        let downloader = HttpDownloader::new(server.uri())
+            .unwrap()
+            .with_retry_policy(fast_retries(3));
+        let mut stream = downloader.download("bundle".to_string()).await.unwrap();
+
+        assert_downloads_fixture(&mut stream, &data).await;
+    }
+
+    #[tokio::test]
+    async fn test_retries_exhausted() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/tx/bundle/data"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(4)
+            .mount(&server)
+            .await;
+
+        let downloader = HttpDownloader::new(server.uri())
+            .unwrap()
+            .with_retry_policy(fast_retries(4));
+        let result = downloader.download("bundle".to_string()).await;
+
+        assert!(matches!(
+            result,
+            Err(ClientError::RetriesExhausted { attempts: 4, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_base64() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let mut body = encode_config(&data[..4641], URL_SAFE_NO_PAD);
+        body.push_str("!!!!");
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/tx/bundle/data"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(body))
+            .mount(&server)
+            .await;
+
+        let downloader = HttpDownloader::new(server.uri()).unwrap();
+        let result = downloader.download("bundle".to_string()).await;
+
+        assert!(matches!(
+            result,
+            Err(ClientError::ParsingData(ParseError::InvalidBase64(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_on_client_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/tx/bundle/data"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let downloader = HttpDownloader::new(server.uri())
+            .unwrap()
+            .with_retry_policy(fast_retries(4));
+        let result = downloader.download("bundle".to_string()).await;
+
+        assert!(matches!(result, Err(ClientError::NotFound(_))));
+    }
+
+    /// Server sending the first `cut` bytes of `body` before dropping the
+    /// connection, then honoring `Range` requests. The requested ranges are
+    /// recorded.
+    async fn interrupted_server(body: Vec<u8>, cut: usize) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let ranges = Arc::new(Mutex::new(vec![]));
+        let recorded = ranges.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buffer = [0u8; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = socket.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..n]);
+                }
+                let request = String::from_utf8(request).unwrap();
+                let range = request
+                    .lines()
+                    .find_map(|l| l.strip_prefix("range: bytes="))
+                    .and_then(|r| r.strip_suffix('-'))
+                    .map(|r| r.parse::<usize>().unwrap());
+                let response = match range {
+                    None => {
+                        let head =
+                            format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n", body.len());
+                        [head.as_bytes(), &body[..cut]].concat()
+                    }
+                    Some(start) => {
+                        recorded.lock().unwrap().push(format!("bytes={}-", start));
+                        let head = format!(
+                            "HTTP/1.1 206 Partial Content\r\ncontent-length: {}\r\ncontent-range: bytes {}-{}/{}\r\n\r\n",
+                            body.len() - start,
+                            start,
+                            body.len() - 1,
+                            body.len()
+                        );
+                        [head.as_bytes(), &body[start..]].concat()
+                    }
+                };
+                socket.write_all(&response).await.unwrap();
+                socket.shutdown().await.unwrap();
+            }
+        });
+        (url, ranges)
+    }
+
+    #[tokio::test]
+    async fn test_empty_chunked_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 1024];
+            let _ = socket.read(&mut buffer).await.unwrap();
+            // No Content-Length, so the response cannot be rejected up front
+            let response = "HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n0\r\n\r\n";
+            socket.write_all(response.as_bytes()).await.unwrap();
+            socket.shutdown().await.unwrap();
+        });
+
+        let downloader = HttpDownloader::new(url)
+            .unwrap()
+            .with_retry_policy(fast_retries(1));
+        let result = downloader.download("bundle".to_string()).await;
+
+        assert!(matches!(result, Err(ClientError::MissingData(_))));
+    }
+
+    #[tokio::test]
+    async fn test_resume_interrupted_transfer() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let encoded = encode_config(&data, URL_SAFE_NO_PAD).into_bytes();
+        let cut = encoded.len() / 3;
+        let (url, ranges) = interrupted_server(encoded, cut).await;
+
+        let downloader = HttpDownloader::new(url)
+            .unwrap()
+            .with_retry_policy(fast_retries(2));
+        let mut stream = downloader.download("bundle".to_string()).await.unwrap();
+
+        assert_downloads_fixture(&mut stream, &data).await;
+        assert_eq!(*ranges.lock().unwrap(), vec![format!("bytes={}-", cut)]);
+    }
+}
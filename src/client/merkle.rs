@@ -0,0 +1,191 @@
+use sha2::{Digest, Sha256};
+
+/// Size of the hashes in the chunk tree.
+pub const HASH_SIZE: usize = 32;
+/// Size of the offsets in the chunk tree, encoded as big-endian integers.
+pub const NOTE_SIZE: usize = 32;
+
+/// Position of a chunk within the data of a transaction, proven by its path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkBounds {
+    /// Offset of the first byte of the chunk.
+    pub start: u64,
+    /// Offset after the last byte of the chunk.
+    pub end: u64,
+    /// SHA-256 of the chunk data.
+    pub data_hash: [u8; HASH_SIZE],
+}
+
+/// Check that `data_path` proves that the chunk holding the byte at `offset`
+/// belongs to the data of size `data_size` whose Merkle root is `data_root`,
+/// as done by Arweave nodes.
+///
+/// Branches of the path are `left id || right id || offset` and its leaf is
+/// `data hash || end offset`, each node id being the SHA-256 of the SHA-256
+/// of its parts.
+pub fn validate_path(
+    data_root: &[u8],
+    offset: u64,
+    data_size: u64,
+    data_path: &[u8],
+) -> Option<ChunkBounds> {
+    if data_size == 0 {
+        return None;
+    }
+    let offset = offset.min(data_size - 1);
+    let (mut id, mut path) = (data_root, data_path);
+    let (mut left_bound, mut right_bound) = (0, data_size);
+    while path.len() > HASH_SIZE + NOTE_SIZE {
+        let (left, rest) = path.split_at(HASH_SIZE);
+        let (right, rest) = rest.split_at(HASH_SIZE);
+        let (note, rest) = rest.split_at_checked(NOTE_SIZE)?;
+        if hash_parts(&[left, right, note]) != id {
+            return None;
+        }
+        let note = read_note(note)?;
+        if offset < note {
+            id = left;
+            right_bound = right_bound.min(note);
+        } else {
+            id = right;
+            left_bound = left_bound.max(note);
+        }
+        path = rest;
+    }
+    if path.len() != HASH_SIZE + NOTE_SIZE {
+        return None;
+    }
+    let (data_hash, note) = path.split_at(HASH_SIZE);
+    if hash_parts(&[data_hash, note]) != id {
+        return None;
+    }
+    Some(ChunkBounds {
+        start: left_bound,
+        end: right_bound,
+        data_hash: data_hash.try_into().ok()?,
+    })
+}
+
+/// Check that `chunk` is the chunk proven by `data_path`, returning its bounds.
+pub fn verify_chunk(
+    data_root: &[u8],
+    offset: u64,
+    data_size: u64,
+    data_path: &[u8],
+    chunk: &[u8],
+) -> Option<ChunkBounds> {
+    let bounds = validate_path(data_root, offset, data_size, data_path)?;
+    let valid = bounds.end - bounds.start == chunk.len() as u64
+        && Sha256::digest(chunk).as_slice() == bounds.data_hash;
+    valid.then_some(bounds)
+}
+
+fn hash_parts(parts: &[&[u8]]) -> [u8; HASH_SIZE] {
+    parts
+        .iter()
+        .fold(Sha256::new(), |hasher, part| {
+            hasher.chain_update(Sha256::digest(part))
+        })
+        .finalize()
+        .into()
+}
+
+/// Offsets are 256-bit integers, anything not fitting in a `u64` is invalid.
+fn read_note(note: &[u8]) -> Option<u64> {
+    let (high, low) = note.split_at(NOTE_SIZE - 8);
+    if high.iter().any(|b| *b != 0) {
+        return None;
+    }
+    Some(u64::from_be_bytes(low.try_into().ok()?))
+}
+
+/// Build the chunk tree of `data` split in chunks of `chunk_size`, returning
+/// its root and the path of each chunk.
+#[cfg(test)]
+pub(crate) fn build_tree(data: &[u8], chunk_size: usize) -> ([u8; HASH_SIZE], Vec<Vec<u8>>) {
+    fn note(offset: usize) -> [u8; NOTE_SIZE] {
+        let mut note = [0u8; NOTE_SIZE];
+        note[NOTE_SIZE - 8..].copy_from_slice(&(offset as u64).to_be_bytes());
+        note
+    }
+
+    // Each node is its id, its end offset and the paths of the chunks below it
+    let mut nodes = vec![];
+    let mut end = 0;
+    for chunk in data.chunks(chunk_size) {
+        end += chunk.len();
+        let data_hash: [u8; HASH_SIZE] = Sha256::digest(chunk).into();
+        let path = [data_hash.as_slice(), &note(end)].concat();
+        nodes.push((hash_parts(&[&data_hash, &note(end)]), end, vec![path]));
+    }
+    while nodes.len() > 1 {
+        let mut parents = vec![];
+        let mut level = nodes.into_iter();
+        while let Some((left, left_end, left_paths)) = level.next() {
+            let Some((right, right_end, right_paths)) = level.next() else {
+                parents.push((left, left_end, left_paths));
+                break;
+            };
+            let branch = [left.as_slice(), &right, &note(left_end)].concat();
+            let paths = left_paths
+                .into_iter()
+                .chain(right_paths)
+                .map(|path| [branch.as_slice(), &path].concat())
+                .collect();
+            let id = hash_parts(&[&left, &right, &note(left_end)]);
+            parents.push((id, right_end, paths));
+        }
+        nodes = parents;
+    }
+    let (root, _, paths) = nodes.pop().unwrap();
+    (root, paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_chunks() {
+        let data = (0..1000u32).map(|i| (i * 7) as u8).collect::<Vec<u8>>();
+        let (root, paths) = build_tree(&data, 64);
+        assert_eq!(paths.len(), 16);
+
+        for (i, (chunk, path)) in data.chunks(64).zip(&paths).enumerate() {
+            let start = (i * 64) as u64;
+            for offset in [start, start + chunk.len() as u64 - 1] {
+                let bounds = verify_chunk(&root, offset, data.len() as u64, path, chunk);
+                assert_eq!(
+                    bounds.map(|b| (b.start, b.end)),
+                    Some((start, start + chunk.len() as u64))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_tampered_chunks() {
+        let data = (0..1000u32).map(|i| (i * 7) as u8).collect::<Vec<u8>>();
+        let (root, paths) = build_tree(&data, 64);
+        let size = data.len() as u64;
+
+        let mut chunk = data[64..128].to_vec();
+        chunk[3] ^= 1;
+        assert_eq!(verify_chunk(&root, 64, size, &paths[1], &chunk), None);
+        // Proof of another chunk
+        assert_eq!(
+            verify_chunk(&root, 64, size, &paths[2], &data[128..192]),
+            None
+        );
+        assert_eq!(
+            verify_chunk(&root, 64, size, &paths[1][1..], &data[64..128]),
+            None
+        );
+        let mut root = root;
+        root[0] ^= 1;
+        assert_eq!(
+            verify_chunk(&root, 64, size, &paths[1], &data[64..128]),
+            None
+        );
+    }
+}
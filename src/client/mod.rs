@@ -1,9 +1,42 @@
 use crate::errors::ClientError;
 use crate::transaction::bundle::BundleStream;
+use std::str::FromStr;
 
+pub mod chunks;
+pub mod decoder;
+pub mod gateways;
 pub mod http;
+pub mod merkle;
+pub mod response;
+pub mod retry;
 
 #[async_trait::async_trait]
 pub trait Downloader {
     async fn download(&self, transaction_id: String) -> Result<BundleStream, ClientError>;
+
+    /// Report that a stream returned by [`Downloader::download`] failed after
+    /// the download started, e.g. when the transfer dropped mid-bundle.
+    fn report_failure(&self, _stream: &BundleStream) {}
+}
+
+/// How the data of a bundle is fetched from the gateway.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadMode {
+    /// Whole data from `/tx/{id}/data`, see [`http::HttpDownloader`].
+    #[default]
+    Data,
+    /// Chunk by chunk from `/chunk/{offset}`, see [`chunks::ChunkDownloader`].
+    Chunks,
+}
+
+impl FromStr for DownloadMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "data" => Ok(DownloadMode::Data),
+            "chunks" => Ok(DownloadMode::Chunks),
+            _ => Err(format!("Invalid download mode {}", s)),
+        }
+    }
 }
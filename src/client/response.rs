@@ -0,0 +1,120 @@
+use crate::errors::ClientError;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Response, StatusCode};
+use tokio_stream::StreamExt;
+
+/// Maximum number of characters of an error page kept in [`ClientError::GatewayError`].
+pub const MAX_EXCERPT_LENGTH: usize = 256;
+
+/// Check the status and content type of a gateway response before its body
+/// is parsed, turning error pages into errors.
+pub async fn check_response(response: Response) -> Result<Response, ClientError> {
+    let url = response.url().to_string();
+    match response.status() {
+        StatusCode::NOT_FOUND => return Err(ClientError::NotFound(url)),
+        StatusCode::ACCEPTED => return Err(ClientError::Pending(url)),
+        status if !status.is_success() => {
+            return Err(ClientError::GatewayError {
+                status: status.as_u16(),
+                body: excerpt(response).await,
+            })
+        }
+        _ => {}
+    }
+    if response.content_length() == Some(0) {
+        return Err(ClientError::MissingData(url));
+    }
+    let is_html = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+    if is_html {
+        return Err(ClientError::GatewayError {
+            status: response.status().as_u16(),
+            body: excerpt(response).await,
+        });
+    }
+    Ok(response)
+}
+
+/// Start of the body on a single line, without reading the whole body.
+async fn excerpt(response: Response) -> String {
+    let mut body = vec![];
+    let mut chunks = response.bytes_stream();
+    while let Some(Ok(chunk)) = chunks.next().await {
+        body.extend_from_slice(&chunk);
+        // UTF-8 characters are at most 4 bytes long
+        if body.len() >= MAX_EXCERPT_LENGTH * 4 {
+            break;
+        }
+    }
+    let body = String::from_utf8_lossy(&body);
+    let mut excerpt = body
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_EXCERPT_LENGTH + 1)
+        .collect::<String>();
+    if excerpt.chars().count() > MAX_EXCERPT_LENGTH {
+        excerpt = excerpt.chars().take(MAX_EXCERPT_LENGTH).collect();
+        excerpt.push_str("...");
+    }
+    excerpt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn check(template: ResponseTemplate) -> Result<Response, ClientError> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(template)
+            .mount(&server)
+            .await;
+        let response = reqwest::get(format!("{}/tx/bundle/data", server.uri()))
+            .await
+            .unwrap();
+        check_response(response).await
+    }
+
+    #[tokio::test]
+    async fn test_check_status() {
+        assert!(matches!(
+            check(ResponseTemplate::new(404)).await,
+            Err(ClientError::NotFound(url)) if url.ends_with("/tx/bundle/data")
+        ));
+        assert!(matches!(
+            check(ResponseTemplate::new(202).set_body_string("Pending")).await,
+            Err(ClientError::Pending(_))
+        ));
+        assert!(matches!(
+            check(ResponseTemplate::new(502).set_body_string("Bad\n  gateway")).await,
+            Err(ClientError::GatewayError { status: 502, body }) if body == "Bad gateway"
+        ));
+        assert!(check(ResponseTemplate::new(200).set_body_string("AAAA"))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_check_content() {
+        assert!(matches!(
+            check(ResponseTemplate::new(200)).await,
+            Err(ClientError::MissingData(_))
+        ));
+
+        let page = format!("<html><body>{}</body></html>", "x".repeat(1000));
+        let result = check(ResponseTemplate::new(200).set_body_raw(page, "text/html")).await;
+        let Err(ClientError::GatewayError { status, body }) = result else {
+            panic!("Expected an error page");
+        };
+        assert_eq!(status, 200);
+        assert!(body.starts_with("<html><body>xxx"));
+        assert_eq!(body.len(), MAX_EXCERPT_LENGTH + 3);
+    }
+}
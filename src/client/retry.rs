@@ -0,0 +1,208 @@
+use crate::errors::ClientError;
+use rand::Rng;
+use reqwest::StatusCode;
+use std::time::Duration;
+
+/// When and how long to wait before retrying a failed request.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts made for a request before giving up, the first one included.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Factor applied to the backoff after each failed attempt.
+    pub multiplier: f64,
+    /// Fraction of the backoff, between 0 and 1, that is randomly removed so
+    /// that clients do not retry all at once.
+    pub jitter: f64,
+    pub retryable_statuses: Vec<StatusCode>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.5,
+            retryable_statuses: vec![
+                StatusCode::REQUEST_TIMEOUT,
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy making a single attempt.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn with_backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_retryable_statuses(mut self, retryable_statuses: Vec<StatusCode>) -> Self {
+        self.retryable_statuses = retryable_statuses;
+        self
+    }
+
+    /// Time to wait after the failed `attempt`, starting at 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff = self
+            .initial_backoff
+            .mul_f64(self.multiplier.max(1.0).powi(exponent).min(u32::MAX as f64))
+            .min(self.max_backoff);
+        let jitter = rand::thread_rng().gen_range(0.0..=self.jitter.clamp(0.0, 1.0));
+        backoff.mul_f64(1.0 - jitter)
+    }
+
+    pub fn is_retryable_status(&self, status: StatusCode) -> bool {
+        self.retryable_statuses.contains(&status)
+    }
+
+    /// Whether the error is transient, like a timeout or a dropped connection.
+    pub fn is_retryable(&self, error: &ClientError) -> bool {
+        match error {
+            ClientError::GatewayError { status, .. } => {
+                StatusCode::from_u16(*status).is_ok_and(|status| self.is_retryable_status(status))
+            }
+            ClientError::CommunicationError(e) => match e.status() {
+                Some(status) => self.is_retryable_status(status),
+                None => e.is_timeout() || e.is_connect() || e.is_request() || e.is_body(),
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Attempts made so far for a request, following a [`RetryPolicy`].
+#[derive(Debug)]
+pub(crate) struct Retries<'a> {
+    policy: &'a RetryPolicy,
+    description: String,
+    failed_attempts: u32,
+}
+
+impl<'a> Retries<'a> {
+    pub fn new(policy: &'a RetryPolicy, description: String) -> Self {
+        Self {
+            policy,
+            description,
+            failed_attempts: 0,
+        }
+    }
+
+    /// Wait before the next attempt if `error` can be retried, otherwise give
+    /// the error back, wrapped with the number of attempts made when retried.
+    pub async fn retry(&mut self, error: ClientError) -> Result<(), ClientError> {
+        self.failed_attempts += 1;
+        if !self.policy.is_retryable(&error) {
+            return Err(self.give_up(error));
+        }
+        if self.failed_attempts >= self.policy.max_attempts {
+            log::error!(
+                "{} failed after {} attempts: {}",
+                self.description,
+                self.failed_attempts,
+                error
+            );
+            return Err(self.give_up(error));
+        }
+        let backoff = self.policy.backoff(self.failed_attempts);
+        log::warn!(
+            "{} failed (attempt {}/{}), retrying in {:?}: {}",
+            self.description,
+            self.failed_attempts,
+            self.policy.max_attempts,
+            backoff,
+            error
+        );
+        tokio::time::sleep(backoff).await;
+        Ok(())
+    }
+
+    fn give_up(&self, error: ClientError) -> ClientError {
+        if self.failed_attempts > 1 {
+            ClientError::RetriesExhausted {
+                attempts: self.failed_attempts,
+                source: Box::new(error),
+            }
+        } else {
+            error
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default()
+            .with_backoff(Duration::from_millis(100), Duration::from_secs(1))
+            .with_jitter(0.0);
+
+        let backoffs = (1..=6).map(|a| policy.backoff(a)).collect::<Vec<_>>();
+        assert_eq!(
+            backoffs,
+            [100, 200, 400, 800, 1000, 1000].map(Duration::from_millis)
+        );
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_backoff_jitter() {
+        let policy = RetryPolicy::default()
+            .with_backoff(Duration::from_millis(100), Duration::from_secs(1))
+            .with_jitter(0.5);
+
+        for attempt in 1..10 {
+            let backoff = policy.backoff(attempt);
+            let max = policy.clone().with_jitter(0.0).backoff(attempt);
+            assert!(backoff <= max && backoff >= max / 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_on_invalid_response() {
+        let policy = RetryPolicy::default()
+            .with_max_attempts(3)
+            .with_backoff(Duration::ZERO, Duration::ZERO);
+        let mut retries = Retries::new(&policy, "test".to_string());
+        let error = || ClientError::InvalidResponse("test".to_string());
+
+        assert!(matches!(
+            retries.retry(error()).await,
+            Err(ClientError::InvalidResponse(_))
+        ));
+    }
+}
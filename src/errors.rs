@@ -3,6 +3,8 @@ use std::string::FromUtf8Error;
 use base64::DecodeError;
 use thiserror::Error;
 
+use crate::transaction::validation::TagViolation;
+
 #[derive(Debug, Eq, PartialEq, Error)]
 pub enum ParseError {
     #[error("Expected Long value but cannot be extracted")]
@@ -15,8 +17,81 @@ pub enum ParseError {
     InvalidSignatureType(u16),
     #[error("Invalid Presence Byte {0}")]
     InvalidPresenceByte(u8),
-    #[error("Invalid Tags Length. Expected {0} - Parsed {0}")]
+    #[error("Invalid Tags Length. Expected {0} - Parsed {1}")]
     InvalidTagsLength(u64, usize),
+    #[error("Long value does not fit in 64 bits")]
+    LongOverflow,
+    #[error("Invalid Tags Block Size. Expected {0} - Parsed {1}")]
+    InvalidBlockSize(i64, usize),
+    #[error("Tags violate ANS-104 limits {0:?}")]
+    TagViolations(Vec<TagViolation>),
+    #[error("Error reading bundle - {0}")]
+    Io(String),
+    #[error("Unexpected end of data reading {field} at offset {offset}")]
+    UnexpectedEof { field: &'static str, offset: usize },
+    #[error("Item ID {header_id} does not match signature hash {computed_id}")]
+    IdMismatch {
+        header_id: String,
+        computed_id: String,
+    },
+    #[error("Invalid signature for item {0}")]
+    InvalidSignature(String),
+    #[error("Invalid Base64 data {0}")]
+    InvalidBase64(#[from] DecodeError),
+    #[error("Invalid length for {field}. Expected {expected} - Found {actual}")]
+    InvalidFieldLength {
+        field: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("Value of {field} at offset {offset} does not fit in 32 bits")]
+    HeaderOverflow { field: &'static str, offset: usize },
+    #[error("Missing {0} to serialize the item")]
+    MissingField(&'static str),
+    #[error("Error downloading bundle - {0}")]
+    Download(DownloadError),
+}
+
+/// Error of the downloader that interrupted a bundle stream. Errors are
+/// compared by message since transport errors cannot be compared.
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub struct DownloadError(Box<ClientError>);
+
+impl DownloadError {
+    pub fn client_error(&self) -> &ClientError {
+        &self.0
+    }
+}
+
+impl PartialEq for DownloadError {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_string() == other.0.to_string()
+    }
+}
+
+impl Eq for DownloadError {}
+
+impl From<std::io::Error> for ParseError {
+    /// Errors of the downloader feeding the bundle are carried as the source
+    /// of the I/O error, they are extracted to keep their type.
+    fn from(e: std::io::Error) -> Self {
+        let message = e.to_string();
+        match e.into_inner().map(|e| e.downcast::<ClientError>()) {
+            Some(Ok(e)) => ParseError::from(*e),
+            _ => ParseError::Io(message),
+        }
+    }
+}
+
+impl From<ClientError> for ParseError {
+    fn from(e: ClientError) -> Self {
+        match e {
+            ClientError::ParsingData(e) => e,
+            ClientError::DecodeBase64Error(e) => ParseError::InvalidBase64(e),
+            e => ParseError::Download(DownloadError(Box::new(e))),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -37,6 +112,36 @@ pub enum ClientError {
     CommunicationError(#[from] reqwest::Error),
     #[error("Parse Url Error - {0}")]
     ParseUrl(String),
+    #[error("Invalid response from gateway - {0}")]
+    InvalidResponse(String),
+    #[error("{0} was not found on the gateway")]
+    NotFound(String),
+    #[error("{0} is pending on the gateway, its transaction is not confirmed yet")]
+    Pending(String),
+    #[error("Gateway answered with status {status} - {body}")]
+    GatewayError { status: u16, body: String },
+    #[error("{0} returned no data")]
+    MissingData(String),
+    #[error("Chunk at offset {0} does not match the data root of the transaction")]
+    InvalidChunkProof(u64),
+    #[error("Giving up after {attempts} attempts - {source}")]
+    RetriesExhausted {
+        attempts: u32,
+        source: Box<ClientError>,
+    },
+    #[error("All gateways failed - {}", format_failures(.0))]
+    AllGatewaysFailed(Vec<(String, ClientError)>),
+}
+
+fn format_failures(failures: &[(String, ClientError)]) -> String {
+    if failures.is_empty() {
+        return "no gateway configured".to_string();
+    }
+    failures
+        .iter()
+        .map(|(url, error)| format!("{}: {}", url, error))
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 #[derive(Debug, Error)]
@@ -1,23 +1,58 @@
 use crate::client::http::HttpDownloader;
 use crate::client::Downloader;
-use crate::errors::IndexerError;
-use crate::storage::fs::LocalStorageFS;
-use crate::storage::Storage;
+use crate::errors::{IndexerError, ParseError};
+use crate::storage::fs::{LocalStorageFS, StorageOptions};
+use crate::storage::quarantine::Quarantine;
+use crate::storage::{FailedItem, Storage};
+use crate::transaction::bundle::{BundleItem, BundleStream, ParseOptions};
 use std::path::PathBuf;
+use std::str::FromStr;
 use tokio_stream::StreamExt;
 
+/// Default maximum nesting level of bundles that are indexed recursively.
+pub const DEFAULT_MAX_BUNDLE_DEPTH: usize = 3;
+
+/// What to do when an item of a bundle fails to parse. Failing to read the
+/// bundle itself, because it is truncated, its download fails or its header
+/// table is invalid, always rolls it back along with the items it quarantined.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Roll back the whole bundle.
+    #[default]
+    FailFast,
+    /// Record the failure in a sidecar error log and keep indexing the rest of the bundle.
+    Skip,
+    /// Same as `Skip` but the raw bytes of the failed items are also kept in
+    /// a [`Quarantine`] so they can be replayed later.
+    Quarantine,
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail-fast" => Ok(ErrorPolicy::FailFast),
+            "skip" => Ok(ErrorPolicy::Skip),
+            "quarantine" => Ok(ErrorPolicy::Quarantine),
+            _ => Err(format!("Invalid error policy {}", s)),
+        }
+    }
+}
+
 pub struct Indexer<D> {
     downloader: D,
     storage_folder: PathBuf,
+    parse_options: ParseOptions,
+    max_bundle_depth: usize,
+    error_policy: ErrorPolicy,
+    storage_options: StorageOptions,
 }
 
 impl Indexer<HttpDownloader> {
     pub async fn new(url_download: &str, storage_folder: &str) -> Result<Self, IndexerError> {
         let downloader = HttpDownloader::new(url_download.to_string())?;
-        Ok(Self {
-            downloader,
-            storage_folder: storage_folder.into(),
-        })
+        Ok(Self::with_downloader(downloader, storage_folder))
     }
 }
 
@@ -25,26 +60,398 @@ impl<D> Indexer<D>
 where
     D: Downloader,
 {
+    pub fn with_downloader(downloader: D, storage_folder: &str) -> Self {
+        Self {
+            downloader,
+            storage_folder: storage_folder.into(),
+            parse_options: ParseOptions::default(),
+            max_bundle_depth: DEFAULT_MAX_BUNDLE_DEPTH,
+            error_policy: ErrorPolicy::default(),
+            storage_options: StorageOptions::default(),
+        }
+    }
+
+    pub fn with_parse_options(mut self, parse_options: ParseOptions) -> Self {
+        self.parse_options = parse_options;
+        self
+    }
+
+    /// Nested bundles deeper than `max_bundle_depth` are stored as opaque items.
+    pub fn with_max_bundle_depth(mut self, max_bundle_depth: usize) -> Self {
+        self.max_bundle_depth = max_bundle_depth;
+        self
+    }
+
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    pub fn with_storage_options(mut self, storage_options: StorageOptions) -> Self {
+        self.storage_options = storage_options;
+        self
+    }
+
     pub async fn index(&self, transaction_id: String) -> Result<(), IndexerError> {
-        let mut storage =
-            LocalStorageFS::new(transaction_id.clone(), self.storage_folder.clone()).await?;
-        let mut items = self.downloader.download(transaction_id).await?;
-        while let Some(item) = items.next().await {
-            match item {
-                Ok(value) => match storage.store(value).await {
-                    Err(e) => {
-                        storage.rollback().await;
-                        return Err(IndexerError::Storage(e));
-                    }
-                    Ok(_) => continue,
-                },
-                Err(e) => {
-                    storage.rollback().await;
-                    return Err(IndexerError::Parser(e));
+        let mut storage = LocalStorageFS::new(transaction_id.clone(), self.storage_folder.clone())
+            .await?
+            .with_options(self.storage_options);
+        let mut quarantine = Quarantine::new(&self.storage_folder, &transaction_id);
+        let items = self
+            .downloader
+            .download(transaction_id)
+            .await?
+            .with_options(self.parse_options);
+        let mut streams = vec![items];
+        while let Some(items) = streams.last_mut() {
+            let result = match items.next().await {
+                Some(Ok(mut value)) => {
+                    let nested = self.nested_stream(&mut value);
+                    let stored = storage.store(value).await.map_err(IndexerError::Storage);
+                    streams.extend(nested);
+                    stored
+                }
+                Some(Err(e)) => {
+                    self.handle_error(&mut storage, &mut quarantine, items, e)
+                        .await
                 }
+                None => {
+                    streams.pop();
+                    Ok(())
+                }
+            };
+            if let Err(e) = result {
+                storage.rollback().await;
+                quarantine.rollback().await;
+                return Err(e);
             }
         }
         storage.commit().await?;
         Ok(())
     }
+
+    /// Stream of the items of `item` when it is a nested bundle to index. Items
+    /// whose data is not a valid bundle are kept as opaque items, recording why.
+    fn nested_stream(&self, item: &mut BundleItem) -> Option<BundleStream> {
+        if item.depth() >= self.max_bundle_depth || !item.is_bundle() {
+            return None;
+        }
+        match item.stream_nested() {
+            Ok(nested) => Some(nested.with_options(self.parse_options)),
+            Err(e) => {
+                log::warn!("Item {} is not a valid nested bundle: {}", item.id(), e);
+                item.set_nested_bundle_error(Some(e.to_string()));
+                None
+            }
+        }
+    }
+
+    async fn handle_error<S: Storage + Send>(
+        &self,
+        storage: &mut S,
+        quarantine: &mut Quarantine,
+        items: &BundleStream,
+        error: ParseError,
+    ) -> Result<(), IndexerError> {
+        // Entries left unread cannot be told apart from a complete bundle once
+        // committed, so reading errors fail under every policy
+        if items.is_interrupted() {
+            self.downloader.report_failure(items);
+            return Err(IndexerError::Parser(error));
+        }
+        let Some(entry) = items.last_entry() else {
+            return Err(IndexerError::Parser(error));
+        };
+        let failed_item = FailedItem {
+            entry: entry.clone(),
+            parent_id: items.parent_id().map(str::to_string),
+            error: error.to_string(),
+        };
+        match (self.error_policy, items.last_item()) {
+            (ErrorPolicy::FailFast, _) => return Err(IndexerError::Parser(error)),
+            (ErrorPolicy::Quarantine, Some(data)) => quarantine.store(&failed_item, data).await?,
+            _ => {}
+        }
+        Ok(storage.store_error(failed_item).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ClientError;
+    use crate::transaction::builder::BundleBuilder;
+    use crate::transaction::bundle::{BUNDLE_HEADER_SIZE, ENTRY_HEADER_SIZE};
+    use crate::transaction::tags::Tag;
+    use bytes::BytesMut;
+    use std::env::temp_dir;
+
+    struct StaticDownloader {
+        data: Vec<u8>,
+    }
+
+    #[async_trait::async_trait]
+    impl Downloader for StaticDownloader {
+        async fn download(&self, _transaction_id: String) -> Result<BundleStream, ClientError> {
+            Ok(BundleItem::stream(BytesMut::from(self.data.as_slice()))?)
+        }
+    }
+
+    /// Storage folder of a single test, so that tests running concurrently do
+    /// not see each other's files.
+    fn storage_folder(test: &str) -> PathBuf {
+        temp_dir().join(format!("ans104-indexer-{}-{}", std::process::id(), test))
+    }
+
+    async fn index_data(
+        transaction_id: &str,
+        data: Vec<u8>,
+        error_policy: ErrorPolicy,
+    ) -> (Result<(), IndexerError>, PathBuf) {
+        let storage_folder = storage_folder(transaction_id);
+        let indexer = Indexer {
+            downloader: StaticDownloader { data },
+            storage_folder: storage_folder.clone(),
+            parse_options: ParseOptions::default(),
+            max_bundle_depth: DEFAULT_MAX_BUNDLE_DEPTH,
+            error_policy,
+            storage_options: StorageOptions::default(),
+        };
+        let result = indexer.index(transaction_id.to_string()).await;
+        (result, storage_folder)
+    }
+
+    async fn index_corrupted(
+        transaction_id: &str,
+        error_policy: ErrorPolicy,
+    ) -> (Result<(), IndexerError>, PathBuf, Vec<u8>) {
+        let mut data =
+            tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+                .await
+                .unwrap();
+        // Invalid signature type on the first item
+        let offset = BUNDLE_HEADER_SIZE + 71 * ENTRY_HEADER_SIZE;
+        data[offset..offset + 2].copy_from_slice(&[0xff, 0xff]);
+        let (result, storage_folder) = index_data(transaction_id, data.clone(), error_policy).await;
+        (result, storage_folder, data)
+    }
+
+    #[tokio::test]
+    async fn test_index_fail_fast() {
+        let (result, storage_folder, _) =
+            index_corrupted("test-index-fail-fast", ErrorPolicy::FailFast).await;
+
+        assert!(matches!(
+            result,
+            Err(IndexerError::Parser(ParseError::InvalidSignatureType(
+                0xffff
+            )))
+        ));
+        assert!(!storage_folder.join("test-index-fail-fast").exists());
+        tokio::fs::remove_dir_all(storage_folder)
+            .await
+            .unwrap_or_default();
+    }
+
+    #[tokio::test]
+    async fn test_index_skip_truncated() {
+        let mut data =
+            tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+                .await
+                .unwrap();
+        let offset = BUNDLE_HEADER_SIZE + 71 * ENTRY_HEADER_SIZE;
+        let size = u32::from_le_bytes(data[32..36].try_into().unwrap()) as usize;
+        // Invalid first item, then cut in the middle of the second item
+        data[offset..offset + 2].copy_from_slice(&[0xff, 0xff]);
+        let truncated = data[..offset + size + 100].to_vec();
+
+        for error_policy in [ErrorPolicy::Skip, ErrorPolicy::Quarantine] {
+            let (result, storage_folder) =
+                index_data("test-index-skip-truncated", truncated.clone(), error_policy).await;
+
+            assert!(matches!(
+                result,
+                Err(IndexerError::Parser(ParseError::UnexpectedEof {
+                    field: "item",
+                    ..
+                }))
+            ));
+            assert!(!storage_folder.join("test-index-skip-truncated").exists());
+            assert!(!storage_folder
+                .join("test-index-skip-truncated.errors")
+                .exists());
+            let quarantine = Quarantine::new(&storage_folder, "test-index-skip-truncated");
+            assert!(quarantine.list().await.unwrap().is_empty());
+            tokio::fs::remove_dir_all(storage_folder)
+                .await
+                .unwrap_or_default();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_index_skip() {
+        let (result, storage_folder, _) =
+            index_corrupted("test-index-skip", ErrorPolicy::Skip).await;
+
+        assert!(result.is_ok());
+        let items = tokio::fs::read_to_string(storage_folder.join("test-index-skip"))
+            .await
+            .unwrap();
+        assert_eq!(items.lines().count(), 70);
+        let errors = tokio::fs::read_to_string(storage_folder.join("test-index-skip.errors"))
+            .await
+            .unwrap();
+        let failed_items = errors
+            .lines()
+            .map(|l| serde_json::from_str::<FailedItem>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(failed_items.len(), 1);
+        assert_eq!(
+            failed_items[0].entry.id,
+            "pt1mPlK8P8ryG5HaWOlbklO3ikOlo1h_kPrzAsQ61iA"
+        );
+        assert_eq!(
+            failed_items[0].entry.offset,
+            BUNDLE_HEADER_SIZE + 71 * ENTRY_HEADER_SIZE
+        );
+        assert_eq!(
+            failed_items[0].error,
+            ParseError::InvalidSignatureType(0xffff).to_string()
+        );
+        tokio::fs::remove_dir_all(storage_folder).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_index_rerun_removes_errors() {
+        let (result, storage_folder, _) =
+            index_corrupted("test-index-rerun", ErrorPolicy::Skip).await;
+        assert!(result.is_ok());
+        let errors_file = storage_folder.join("test-index-rerun.errors");
+        assert!(errors_file.exists());
+
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let (result, _) = index_data("test-index-rerun", data, ErrorPolicy::Skip).await;
+
+        assert!(result.is_ok());
+        assert!(!errors_file.exists());
+        let items = tokio::fs::read_to_string(storage_folder.join("test-index-rerun"))
+            .await
+            .unwrap();
+        assert_eq!(items.lines().count(), 71);
+        tokio::fs::remove_dir_all(storage_folder).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_index_quarantine() {
+        let (result, storage_folder, data) =
+            index_corrupted("test-index-quarantine", ErrorPolicy::Quarantine).await;
+
+        assert!(result.is_ok());
+        let quarantine = Quarantine::new(&storage_folder, "test-index-quarantine");
+        let failed_items = quarantine.list().await.unwrap();
+        assert_eq!(failed_items.len(), 1);
+        let entry = &failed_items[0].entry;
+        let quarantined = tokio::fs::read(quarantine.path(&failed_items[0]).with_extension("bin"))
+            .await
+            .unwrap();
+        assert_eq!(quarantined, &data[entry.offset..entry.offset + entry.size]);
+
+        let replayed = quarantine
+            .replay(&failed_items[0], &ParseOptions::default())
+            .await;
+        assert!(matches!(
+            replayed,
+            Err(IndexerError::Parser(ParseError::InvalidSignatureType(
+                0xffff
+            )))
+        ));
+        tokio::fs::remove_dir_all(storage_folder).await.unwrap();
+    }
+
+    /// Unsigned ED25519 item, `signature` making its ID unique.
+    fn raw_item(signature: u8, tags: &[Tag], data: &[u8]) -> Vec<u8> {
+        let encoded_tags = Tag::encode_all(tags);
+        let mut item = vec![];
+        item.extend_from_slice(&2u16.to_le_bytes());
+        item.extend_from_slice(&[signature; 64]);
+        item.extend_from_slice(&[0u8; 32]);
+        item.extend_from_slice(&[0, 0]);
+        item.extend_from_slice(&(tags.len() as u64).to_le_bytes());
+        item.extend_from_slice(&(encoded_tags.len() as u64).to_le_bytes());
+        item.extend_from_slice(&encoded_tags);
+        item.extend_from_slice(data);
+        item
+    }
+
+    fn bundle(items: Vec<Vec<u8>>) -> Vec<u8> {
+        let mut builder = BundleBuilder::new();
+        for item in items {
+            builder.push_raw_item(item).unwrap();
+        }
+        builder.build().to_vec()
+    }
+
+    async fn index_nested(transaction_id: &str, max_bundle_depth: usize) -> Vec<BundleItem> {
+        let bundle_tags = [
+            Tag::new("Bundle-Format", "binary"),
+            Tag::new("Bundle-Version", "2.0.0"),
+        ];
+        let innermost = bundle(vec![raw_item(5, &[], b"innermost")]);
+        let inner = bundle(vec![
+            raw_item(3, &[], b"inner"),
+            raw_item(4, &bundle_tags, &innermost),
+        ]);
+        let data = bundle(vec![
+            raw_item(1, &[], b"outer"),
+            raw_item(2, &bundle_tags, &inner),
+            raw_item(6, &bundle_tags, b"not a bundle"),
+        ]);
+        let storage_folder = storage_folder(transaction_id);
+        let indexer =
+            Indexer::with_downloader(StaticDownloader { data }, storage_folder.to_str().unwrap())
+                .with_max_bundle_depth(max_bundle_depth);
+
+        indexer.index(transaction_id.to_string()).await.unwrap();
+
+        let stored = tokio::fs::read_to_string(storage_folder.join(transaction_id))
+            .await
+            .unwrap();
+        tokio::fs::remove_dir_all(storage_folder).await.unwrap();
+        stored
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_index_nested() {
+        let items = index_nested("test-index-nested", DEFAULT_MAX_BUNDLE_DEPTH).await;
+
+        let depths = items.iter().map(|i| i.depth()).collect::<Vec<_>>();
+        assert_eq!(depths, [0, 0, 1, 1, 2, 0]);
+        assert_eq!(items[2].parent_id(), Some(items[1].id()));
+        assert_eq!(items[4].parent_id(), Some(items[3].id()));
+        assert_eq!(items[4].data().as_ref(), b"innermost");
+        assert!(items
+            .iter()
+            .take(5)
+            .all(|i| i.nested_bundle_error().is_none()));
+        // Tagged as a bundle but stored as an opaque item, even when failing fast
+        assert!(items[5].is_bundle());
+        assert!(items[5].nested_bundle_error().is_some());
+        assert_eq!(items[5].data().as_ref(), b"not a bundle");
+    }
+
+    #[tokio::test]
+    async fn test_index_nested_max_depth() {
+        let items = index_nested("test-index-nested-max-depth", 1).await;
+
+        let depths = items.iter().map(|i| i.depth()).collect::<Vec<_>>();
+        assert_eq!(depths, [0, 0, 1, 1, 0]);
+        // Deeper bundles are kept as opaque items
+        assert!(items[3].is_bundle());
+        assert!(items[3].nested_bundle_error().is_none());
+    }
 }
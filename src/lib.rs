@@ -1,5 +1,5 @@
-mod client;
+pub mod client;
 pub mod errors;
 pub mod indexer;
-mod storage;
+pub mod storage;
 pub mod transaction;
@@ -1,4 +1,15 @@
-use ans104_indexer::indexer::indexer_default::Indexer;
+use ans104_indexer::client::chunks::ChunkDownloader;
+use ans104_indexer::client::gateways::{MultiGatewayDownloader, MULTI_GATEWAY_MAX_ATTEMPTS};
+use ans104_indexer::client::http::HttpDownloader;
+use ans104_indexer::client::retry::RetryPolicy;
+use ans104_indexer::client::{DownloadMode, Downloader};
+use ans104_indexer::errors::ClientError;
+use ans104_indexer::indexer::indexer_default::{ErrorPolicy, Indexer, DEFAULT_MAX_BUNDLE_DEPTH};
+use ans104_indexer::storage::fs::StorageOptions;
+use ans104_indexer::transaction::bundle::ParseOptions;
+use ans104_indexer::transaction::data::DataEncoding;
+use ans104_indexer::transaction::validation::TagValidation;
+use ans104_indexer::transaction::verify::SignatureVerification;
 use clap::Parser;
 
 #[derive(Parser)]
@@ -12,14 +23,122 @@ pub struct Cli {
     #[clap(short, long, default_value = "./storage")]
     pub storage_folder: String,
 
-    #[clap(short, long, default_value = "https://arweave.net")]
-    pub arwaeve_url: String,
+    /// Gateways in order of preference, separated by commas
+    #[clap(
+        short,
+        long,
+        default_value = "https://arweave.net",
+        use_value_delimiter = true
+    )]
+    pub arwaeve_url: Vec<String>,
+
+    /// Ask the first two gateways at once and keep the fastest
+    #[clap(long)]
+    pub race_gateways: bool,
+
+    /// How the bundle is fetched: data or chunks
+    #[clap(long, default_value = "data")]
+    pub download_mode: DownloadMode,
+
+    /// Attempts made for each request to a gateway before giving up, 5 with
+    /// a single gateway and 2 with several
+    #[clap(long)]
+    pub max_attempts: Option<u32>,
+
+    /// Verify that each item ID matches the SHA-256 of its signature
+    #[clap(long)]
+    pub verify_ids: bool,
+
+    /// Signature verification mode: disabled, record or reject
+    #[clap(long, default_value = "disabled")]
+    pub verify_signatures: SignatureVerification,
+
+    /// Tag validation against ANS-104 limits: disabled, lenient or strict
+    #[clap(long, default_value = "disabled")]
+    pub tag_validation: TagValidation,
+
+    /// Leave the data out of the items, only storing its size and offset
+    #[clap(long)]
+    pub headers_only: bool,
+
+    /// Maximum nesting level of bundles indexed recursively
+    #[clap(long, default_value_t = DEFAULT_MAX_BUNDLE_DEPTH)]
+    pub max_bundle_depth: usize,
+
+    /// What to do with items that fail to parse: fail-fast, skip or quarantine
+    #[clap(long, default_value = "fail-fast")]
+    pub error_policy: ErrorPolicy,
+
+    /// Encoding of the data of the stored items: base64, hex or omit
+    #[clap(long, default_value = "base64")]
+    pub data_encoding: DataEncoding,
+
+    /// Also write the data of each item as a raw file
+    #[clap(long)]
+    pub raw_data: bool,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
     let cli = Cli::parse();
-    let indexer = Indexer::new(&cli.arwaeve_url, &cli.storage_folder).await?;
+    let parse_options = ParseOptions {
+        verify_ids: cli.verify_ids,
+        signature_verification: cli.verify_signatures,
+        tag_validation: cli.tag_validation,
+        headers_only: cli.headers_only,
+    };
+    let storage_options = StorageOptions {
+        data_encoding: cli.data_encoding,
+        raw_data: cli.raw_data,
+    };
+    let mut retry_policy = RetryPolicy::default();
+    if let Some(max_attempts) = cli.max_attempts {
+        retry_policy = retry_policy.with_max_attempts(max_attempts);
+    } else if cli.arwaeve_url.len() > 1 {
+        retry_policy = retry_policy.with_max_attempts(MULTI_GATEWAY_MAX_ATTEMPTS);
+    }
+    match cli.download_mode {
+        DownloadMode::Data => {
+            let downloader = gateways(&cli, |url| {
+                Ok(HttpDownloader::new(url)?.with_retry_policy(retry_policy.clone()))
+            })?;
+            let indexer = Indexer::with_downloader(downloader, &cli.storage_folder);
+            index(indexer, cli, parse_options, storage_options).await
+        }
+        DownloadMode::Chunks => {
+            let downloader = gateways(&cli, |url| {
+                Ok(ChunkDownloader::new(url)?.with_retry_policy(retry_policy.clone()))
+            })?;
+            let indexer = Indexer::with_downloader(downloader, &cli.storage_folder);
+            index(indexer, cli, parse_options, storage_options).await
+        }
+    }
+}
+
+fn gateways<D: Downloader + Sync>(
+    cli: &Cli,
+    downloader: impl Fn(String) -> Result<D, ClientError>,
+) -> Result<MultiGatewayDownloader<D>, ClientError> {
+    let gateways = cli
+        .arwaeve_url
+        .iter()
+        .map(|url| Ok((url.clone(), downloader(url.clone())?)))
+        .collect::<Result<Vec<_>, ClientError>>()?;
+    Ok(MultiGatewayDownloader::new(gateways).with_racing(cli.race_gateways))
+}
+
+async fn index<D: Downloader>(
+    indexer: Indexer<D>,
+    cli: Cli,
+    parse_options: ParseOptions,
+    storage_options: StorageOptions,
+) -> anyhow::Result<()> {
+    let indexer = indexer
+        .with_parse_options(parse_options)
+        .with_max_bundle_depth(cli.max_bundle_depth)
+        .with_error_policy(cli.error_policy)
+        .with_storage_options(storage_options);
     indexer.index(cli.transaction_id).await?;
     Ok(())
 }
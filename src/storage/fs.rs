@@ -1,16 +1,35 @@
 use std::env::temp_dir;
 use std::path::PathBuf;
 
-use super::Storage;
+use super::{FailedItem, Storage};
 use crate::errors::StorageError;
 use crate::transaction::bundle::BundleItem;
+use crate::transaction::data::DataEncoding;
 use tokio::io::{AsyncWrite, AsyncWriteExt};
 
+/// Extension of the sidecar file listing the items that failed to parse.
+pub const ERRORS_EXTENSION: &str = "errors";
+/// Extension of the folder holding the raw data of each item.
+pub const DATA_EXTENSION: &str = "data";
+
+/// How the data of the items is stored.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StorageOptions {
+    /// Encoding of the data in the stored items.
+    pub data_encoding: DataEncoding,
+    /// Also write the data of each item as a raw file named by the item ID.
+    pub raw_data: bool,
+}
+
 pub struct LocalStorageFS<W: AsyncWrite> {
     storage: PathBuf,
     transaction_id: String,
     current_file: PathBuf,
     fs: W,
+    errors_file: PathBuf,
+    errors: Option<tokio::fs::File>,
+    data_folder: PathBuf,
+    options: StorageOptions,
 }
 
 impl LocalStorageFS<tokio::fs::File> {
@@ -19,6 +38,8 @@ impl LocalStorageFS<tokio::fs::File> {
         storage_folder: PathBuf,
     ) -> Result<Self, StorageError> {
         let current_file = temp_dir().join(transaction_id.clone());
+        let errors_file = current_file.with_extension(ERRORS_EXTENSION);
+        let data_folder = current_file.with_extension(DATA_EXTENSION);
         let storage = storage_folder.clone();
         tokio::fs::create_dir_all(storage_folder).await?;
         let fs = tokio::fs::File::options()
@@ -31,13 +52,29 @@ impl LocalStorageFS<tokio::fs::File> {
             transaction_id,
             storage,
             fs,
+            errors_file,
+            errors: None,
+            data_folder,
+            options: StorageOptions::default(),
         })
     }
 }
 
+impl<W: AsyncWrite> LocalStorageFS<W> {
+    pub fn with_options(mut self, options: StorageOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
 #[async_trait::async_trait]
 impl<W: AsyncWrite + Send + Unpin> Storage for LocalStorageFS<W> {
-    async fn store(&mut self, bundle_item: BundleItem) -> Result<(), StorageError> {
+    async fn store(&mut self, mut bundle_item: BundleItem) -> Result<(), StorageError> {
+        if self.options.raw_data && bundle_item.has_data() {
+            tokio::fs::create_dir_all(&self.data_folder).await?;
+            tokio::fs::write(self.data_folder.join(bundle_item.id()), bundle_item.data()).await?;
+        }
+        bundle_item.set_data_encoding(self.options.data_encoding);
         let mut bytes = serde_json::to_vec(&bundle_item)
             .map_err(|e| StorageError::CannotSerializeItem(e.to_string()))?;
         bytes.push(b'\n');
@@ -46,14 +83,111 @@ impl<W: AsyncWrite + Send + Unpin> Storage for LocalStorageFS<W> {
         Ok(())
     }
 
+    async fn store_error(&mut self, failed_item: FailedItem) -> Result<(), StorageError> {
+        let mut bytes = serde_json::to_vec(&failed_item)
+            .map_err(|e| StorageError::CannotSerializeItem(e.to_string()))?;
+        bytes.push(b'\n');
+        let errors = match self.errors.as_mut() {
+            Some(errors) => errors,
+            None => self.errors.insert(
+                tokio::fs::File::options()
+                    .append(true)
+                    .create(true)
+                    .open(self.errors_file.clone())
+                    .await?,
+            ),
+        };
+        errors.write_all(&bytes).await?;
+        errors.flush().await?;
+        Ok(())
+    }
+
     async fn commit(self) -> Result<(), StorageError> {
+        let errors_file = self
+            .storage
+            .join(&self.transaction_id)
+            .with_extension(ERRORS_EXTENSION);
+        if self.errors.is_some() {
+            tokio::fs::rename(self.errors_file, errors_file).await?;
+        } else {
+            // Do not leave the failures of a previous run next to the new items
+            remove_if_exists(tokio::fs::remove_file(errors_file).await)?;
+        }
+        let data_folder = self
+            .storage
+            .join(&self.transaction_id)
+            .with_extension(DATA_EXTENSION);
+        remove_if_exists(tokio::fs::remove_dir_all(&data_folder).await)?;
+        if self.options.raw_data && tokio::fs::try_exists(&self.data_folder).await? {
+            tokio::fs::rename(self.data_folder, data_folder).await?;
+        }
         tokio::fs::rename(self.current_file, self.storage.join(self.transaction_id)).await?;
         Ok(())
     }
 
     async fn rollback(self) {
+        if self.options.raw_data {
+            tokio::fs::remove_dir_all(self.data_folder)
+                .await
+                .unwrap_or_default()
+        }
+        if self.errors.is_some() {
+            tokio::fs::remove_file(self.errors_file)
+                .await
+                .unwrap_or_default()
+        }
         tokio::fs::remove_file(self.current_file)
             .await
             .unwrap_or_default()
     }
 }
+
+fn remove_if_exists(result: std::io::Result<()>) -> std::io::Result<()> {
+    match result {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn test_store_raw_data() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let mut stream = BundleItem::stream(BytesMut::from(data.as_slice())).unwrap();
+        let item = stream.next().await.unwrap().unwrap();
+        let (id, item_data) = (item.id().to_string(), item.data().clone());
+        let storage_folder = temp_dir().join("ans104-storage-tests");
+        let options = StorageOptions {
+            data_encoding: DataEncoding::Omit,
+            raw_data: true,
+        };
+        let mut storage = LocalStorageFS::new("test-raw-data".to_string(), storage_folder.clone())
+            .await
+            .unwrap()
+            .with_options(options);
+
+        storage.store(item).await.unwrap();
+        storage.commit().await.unwrap();
+
+        let stored = tokio::fs::read_to_string(storage_folder.join("test-raw-data"))
+            .await
+            .unwrap();
+        let stored: serde_json::Value = serde_json::from_str(stored.trim_end()).unwrap();
+        assert_eq!(stored["id"], id.as_str());
+        assert!(stored.get("data").is_none());
+        let data_folder = storage_folder.join("test-raw-data.data");
+        let raw = tokio::fs::read(data_folder.join(&id)).await.unwrap();
+        assert_eq!(raw, item_data);
+        tokio::fs::remove_dir_all(data_folder).await.unwrap();
+        tokio::fs::remove_file(storage_folder.join("test-raw-data"))
+            .await
+            .unwrap();
+    }
+}
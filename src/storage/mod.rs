@@ -1,12 +1,36 @@
 pub mod fs;
+pub mod quarantine;
 
 use crate::errors::StorageError;
-use crate::transaction::bundle::BundleItem;
+use crate::transaction::bundle::{BundleEntry, BundleItem};
+use serde::{Deserialize, Serialize};
+
+/// Record of an item that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedItem {
+    #[serde(flatten)]
+    pub entry: BundleEntry,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    pub error: String,
+}
 
 #[async_trait::async_trait]
 pub trait Storage {
     async fn store(&mut self, bundle_item: BundleItem) -> Result<(), StorageError>;
 
+    /// Record an item that could not be parsed. Failures are only logged by
+    /// default.
+    async fn store_error(&mut self, failed_item: FailedItem) -> Result<(), StorageError> {
+        log::warn!(
+            "Item {} at offset {} failed to parse: {}",
+            failed_item.entry.id,
+            failed_item.entry.offset,
+            failed_item.error
+        );
+        Ok(())
+    }
+
     async fn commit(self) -> Result<(), StorageError>;
 
     async fn rollback(self);
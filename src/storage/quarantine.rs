@@ -0,0 +1,194 @@
+use std::path::{Path, PathBuf};
+
+use super::FailedItem;
+use crate::errors::{IndexerError, StorageError};
+use crate::transaction::bundle::{BundleItem, ParseOptions};
+use bytes::Bytes;
+
+/// Folder inside the storage folder holding the quarantined items of each bundle.
+pub const QUARANTINE_FOLDER: &str = "quarantine";
+/// Extension of the raw bytes of a quarantined item.
+pub const DATA_EXTENSION: &str = "bin";
+/// Extension of the error record of a quarantined item.
+pub const RECORD_EXTENSION: &str = "json";
+
+/// Keeps the raw bytes of the items of a bundle that failed to parse, named by
+/// their parent ID, offset and header ID, next to a [`FailedItem`] record, so
+/// they can be inspected and parsed again later.
+pub struct Quarantine {
+    folder: PathBuf,
+    /// Items stored by this instance, removed on rollback.
+    stored: Vec<FailedItem>,
+}
+
+impl Quarantine {
+    pub fn new(storage_folder: &Path, transaction_id: &str) -> Self {
+        Self {
+            folder: storage_folder.join(QUARANTINE_FOLDER).join(transaction_id),
+            stored: vec![],
+        }
+    }
+
+    pub fn folder(&self) -> &Path {
+        &self.folder
+    }
+
+    pub async fn store(
+        &mut self,
+        failed_item: &FailedItem,
+        data: &[u8],
+    ) -> Result<(), StorageError> {
+        let record = serde_json::to_vec_pretty(failed_item)
+            .map_err(|e| StorageError::CannotSerializeItem(e.to_string()))?;
+        tokio::fs::create_dir_all(&self.folder).await?;
+        let path = self.path(failed_item);
+        tokio::fs::write(path.with_extension(DATA_EXTENSION), data).await?;
+        tokio::fs::write(path.with_extension(RECORD_EXTENSION), record).await?;
+        self.stored.push(failed_item.clone());
+        Ok(())
+    }
+
+    /// Path of a quarantined item, without extension. The same ID can appear
+    /// several times in a bundle and in different nested bundles, so the
+    /// parent ID and offset are part of the name.
+    pub(crate) fn path(&self, failed_item: &FailedItem) -> PathBuf {
+        let entry = &failed_item.entry;
+        let name = match &failed_item.parent_id {
+            Some(parent_id) => format!("{}-{}-{}", parent_id, entry.offset, entry.id),
+            None => format!("{}-{}", entry.offset, entry.id),
+        };
+        self.folder.join(name)
+    }
+
+    /// Remove the items stored by this instance, when the bundle they belong
+    /// to is rolled back. Items quarantined by previous runs are kept.
+    pub async fn rollback(self) {
+        for failed_item in &self.stored {
+            self.remove(failed_item).await.unwrap_or_default();
+        }
+    }
+
+    /// Records of all the quarantined items of the bundle.
+    pub async fn list(&self) -> Result<Vec<FailedItem>, StorageError> {
+        let mut failed_items = vec![];
+        let mut entries = match tokio::fs::read_dir(&self.folder).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(failed_items),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(RECORD_EXTENSION) {
+                continue;
+            }
+            let record = tokio::fs::read(path).await?;
+            failed_items.push(
+                serde_json::from_slice(&record)
+                    .map_err(|e| StorageError::CannotSerializeItem(e.to_string()))?,
+            );
+        }
+        Ok(failed_items)
+    }
+
+    /// Parse a quarantined item again, e.g. once the parser has been fixed.
+    pub async fn replay(
+        &self,
+        failed_item: &FailedItem,
+        options: &ParseOptions,
+    ) -> Result<BundleItem, IndexerError> {
+        let path = self.path(failed_item);
+        let data = tokio::fs::read(path.with_extension(DATA_EXTENSION))
+            .await
+            .map_err(StorageError::from)?;
+        let mut item = BundleItem::parse(Bytes::from(data), &failed_item.entry, options)?;
+        item.set_parent_id(failed_item.parent_id.clone());
+        Ok(item)
+    }
+
+    /// Remove a quarantined item, e.g. once it has been replayed successfully.
+    pub async fn remove(&self, failed_item: &FailedItem) -> Result<(), StorageError> {
+        let path = self.path(failed_item);
+        tokio::fs::remove_file(path.with_extension(DATA_EXTENSION)).await?;
+        tokio::fs::remove_file(path.with_extension(RECORD_EXTENSION)).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::bundle::{BundleEntry, BUNDLE_HEADER_SIZE, ENTRY_HEADER_SIZE};
+    use std::env::temp_dir;
+
+    #[tokio::test]
+    async fn test_store_and_replay() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let size = u32::from_le_bytes(data[32..36].try_into().unwrap()) as usize;
+        let offset = BUNDLE_HEADER_SIZE + 71 * ENTRY_HEADER_SIZE;
+        let failed_item = FailedItem {
+            entry: BundleEntry {
+                id: "pt1mPlK8P8ryG5HaWOlbklO3ikOlo1h_kPrzAsQ61iA".to_string(),
+                offset,
+                size,
+            },
+            parent_id: Some("parent".to_string()),
+            error: "Invalid signature type 65535".to_string(),
+        };
+        let mut quarantine = Quarantine::new(&temp_dir().join("ans104-quarantine-tests"), "bundle");
+
+        quarantine
+            .store(&failed_item, &data[offset..offset + size])
+            .await
+            .unwrap();
+        assert_eq!(quarantine.list().await.unwrap(), vec![failed_item.clone()]);
+
+        let item = quarantine
+            .replay(&failed_item, &ParseOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(item.to_bytes().unwrap(), &data[offset..offset + size]);
+        assert_eq!(item.parent_id(), Some("parent"));
+
+        quarantine.remove(&failed_item).await.unwrap();
+        assert!(quarantine.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_store_same_id() {
+        let entry = |offset| BundleEntry {
+            id: "pt1mPlK8P8ryG5HaWOlbklO3ikOlo1h_kPrzAsQ61iA".to_string(),
+            offset,
+            size: 4,
+        };
+        let failed_items = [
+            (None, 100, b"root"),
+            (None, 200, b"same"),
+            (Some("parent".to_string()), 100, b"nest"),
+        ]
+        .map(|(parent_id, offset, data)| {
+            let failed_item = FailedItem {
+                entry: entry(offset),
+                parent_id,
+                error: "Invalid signature type 65535".to_string(),
+            };
+            (failed_item, data)
+        });
+        let storage_folder = temp_dir().join("ans104-quarantine-tests");
+        let mut quarantine = Quarantine::new(&storage_folder, "same-id");
+
+        for (failed_item, data) in &failed_items {
+            quarantine.store(failed_item, *data).await.unwrap();
+        }
+
+        assert_eq!(quarantine.list().await.unwrap().len(), 3);
+        for (failed_item, data) in &failed_items {
+            let path = quarantine.path(failed_item).with_extension(DATA_EXTENSION);
+            assert_eq!(&tokio::fs::read(path).await.unwrap(), *data);
+        }
+        quarantine.rollback().await;
+        let quarantine = Quarantine::new(&storage_folder, "same-id");
+        assert!(quarantine.list().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+use super::bundle::SignatureType;
+use base64::{encode_config, URL_SAFE_NO_PAD};
+use sha2::{Digest, Sha256};
+use sha3::{Keccak256, Sha3_256};
+
+/// Size of an Aptos public key within a multi-signature owner.
+const APTOS_KEY_SIZE: usize = 32;
+
+/// Wallet address of the owner of a data item, in the native format of its
+/// signature scheme:
+///
+/// - Arweave: base64url SHA-256 of the RSA modulus.
+/// - Ethereum: EIP-55 checksummed address of the public key.
+/// - ED25519 and Solana: base58 public key.
+/// - Aptos: hex SHA3-256 authentication key.
+///
+/// Starknet account addresses depend on the account contract and cannot be
+/// derived from the key, so none is given.
+pub fn owner_address(signature_type: SignatureType, owner: &[u8]) -> Option<String> {
+    match signature_type {
+        SignatureType::Arweave => Some(arweave_address(owner)),
+        SignatureType::Ethereum => Some(ethereum_address(owner)),
+        SignatureType::Typedethereum => typed_ethereum_address(owner),
+        SignatureType::ED25519 | SignatureType::Solana => Some(bs58::encode(owner).into_string()),
+        SignatureType::Injectedaptos => Some(aptos_address(owner, 0)),
+        SignatureType::Multiaptos => Some(multi_aptos_address(owner)),
+        SignatureType::Starknet => None,
+    }
+}
+
+fn arweave_address(owner: &[u8]) -> String {
+    encode_config(Sha256::digest(owner), URL_SAFE_NO_PAD)
+}
+
+fn ethereum_address(owner: &[u8]) -> String {
+    // Uncompressed SEC1 key, the address is derived from the key without its 0x04 prefix
+    let key = owner.strip_prefix(&[4]).unwrap_or(owner);
+    let hash = Keccak256::digest(key);
+    checksum_address(&hex::encode(&hash[12..]))
+}
+
+/// Typed Ethereum signers use the ASCII address itself as owner.
+fn typed_ethereum_address(owner: &[u8]) -> Option<String> {
+    match std::str::from_utf8(owner)
+        .ok()
+        .and_then(|owner| owner.strip_prefix("0x"))
+    {
+        Some(address) if address.len() == 40 && hex::decode(address).is_ok() => {
+            Some(checksum_address(&address.to_ascii_lowercase()))
+        }
+        _ => None,
+    }
+}
+
+/// Mixed-case checksum encoding of a lowercase hex address, as defined by EIP-55.
+fn checksum_address(address: &str) -> String {
+    let hash = hex::encode(Keccak256::digest(address.as_bytes()));
+    let checksummed = address
+        .chars()
+        .zip(hash.chars())
+        .map(|(c, h)| if h >= '8' { c.to_ascii_uppercase() } else { c })
+        .collect::<String>();
+    format!("0x{}", checksummed)
+}
+
+fn aptos_address(key: &[u8], scheme: u8) -> String {
+    let hash = Sha3_256::new()
+        .chain_update(key)
+        .chain_update([scheme])
+        .finalize();
+    format!("0x{}", hex::encode(hash))
+}
+
+/// Multi-signature owners are 32 public keys, padded with zeros when fewer are
+/// used, followed by the signature threshold.
+fn multi_aptos_address(owner: &[u8]) -> String {
+    let (keys, threshold) = owner.split_at(owner.len().saturating_sub(1));
+    let mut key = keys
+        .chunks(APTOS_KEY_SIZE)
+        .filter(|k| k.iter().any(|b| *b != 0))
+        .flatten()
+        .copied()
+        .collect::<Vec<u8>>();
+    key.extend_from_slice(threshold);
+    aptos_address(&key, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum_address() {
+        // Vectors from EIP-55
+        for address in [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ] {
+            assert_eq!(checksum_address(&address[2..].to_lowercase()), address);
+        }
+    }
+
+    #[test]
+    fn test_ethereum_address() {
+        // Key of the private key 1, the generator point
+        let mut secret = [0u8; 32];
+        secret[31] = 1;
+        let key = k256::ecdsa::SigningKey::from_slice(&secret).unwrap();
+        let owner = key.verifying_key().to_encoded_point(false);
+
+        assert_eq!(
+            owner_address(SignatureType::Ethereum, owner.as_bytes()).unwrap(),
+            "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf"
+        );
+        assert_eq!(
+            owner_address(
+                SignatureType::Typedethereum,
+                b"0x7e5f4552091a69125d5dfcb7b8c2659029395bdf"
+            )
+            .unwrap(),
+            "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf"
+        );
+        assert_eq!(
+            owner_address(SignatureType::Typedethereum, &[4u8; 42]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_arweave_address() {
+        let owner = [1u8; 512];
+        assert_eq!(
+            owner_address(SignatureType::Arweave, &owner).unwrap(),
+            encode_config(Sha256::digest(owner), URL_SAFE_NO_PAD)
+        );
+    }
+
+    #[test]
+    fn test_starknet_address() {
+        assert_eq!(owner_address(SignatureType::Starknet, &[2u8; 33]), None);
+    }
+
+    #[test]
+    fn test_solana_address() {
+        let owner = [0u8; 32];
+        assert_eq!(
+            owner_address(SignatureType::Solana, &owner).unwrap(),
+            "11111111111111111111111111111111"
+        );
+    }
+
+    #[test]
+    fn test_multi_aptos_address() {
+        let mut owner = vec![0u8; 1025];
+        owner[..32].copy_from_slice(&[1u8; 32]);
+        owner[32..64].copy_from_slice(&[2u8; 32]);
+        owner[1024] = 2;
+        let mut key = [[1u8; 32], [2u8; 32]].concat();
+        key.push(2);
+
+        assert_eq!(
+            owner_address(SignatureType::Multiaptos, &owner).unwrap(),
+            aptos_address(&key, 1)
+        );
+    }
+}
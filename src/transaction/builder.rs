@@ -0,0 +1,300 @@
+use super::bundle::{
+    write_item, BundleEntry, BundleItem, ParseOptions, SignatureType, BUNDLE_HEADER_SIZE,
+    ENTRY_HEADER_SIZE, ID_SIZE,
+};
+use super::deep_hash::DEEP_HASH_SIZE;
+use super::tags::Tag;
+use super::verify::{signature_data, SignedFields};
+use crate::errors::ParseError;
+use base64::{encode_config, URL_SAFE_NO_PAD};
+use bytes::{BufMut, Bytes, BytesMut};
+
+type Result<T> = std::result::Result<T, ParseError>;
+
+/// Serializes data items into an ANS-104 bundle.
+#[derive(Debug, Default)]
+pub struct BundleBuilder {
+    items: Vec<([u8; ID_SIZE], Bytes)>,
+}
+
+impl BundleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a signed item, keeping its ID.
+    pub fn push_item(&mut self, item: &BundleItem) -> Result<()> {
+        let id = item.decoded_id()?;
+        self.items.push((id, Bytes::from(item.to_bytes()?)));
+        Ok(())
+    }
+
+    /// Add an item already serialized in binary format. Its ID is computed from
+    /// the signature.
+    pub fn push_raw_item(&mut self, data: impl Into<Bytes>) -> Result<()> {
+        let data = data.into();
+        let id = BundleItem::raw_item_id(&data)?;
+        self.items.push((id, data));
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn build(self) -> Bytes {
+        let items_size = self.items.iter().map(|(_, data)| data.len()).sum::<usize>();
+        let mut bundle = BytesMut::with_capacity(
+            BUNDLE_HEADER_SIZE + self.items.len() * ENTRY_HEADER_SIZE + items_size,
+        );
+        // Counts and sizes are 256 bit little endian integers
+        bundle.put_u64_le(self.items.len() as u64);
+        bundle.put_bytes(0, 24);
+        for (id, data) in &self.items {
+            bundle.put_u64_le(data.len() as u64);
+            bundle.put_bytes(0, 24);
+            bundle.extend_from_slice(id);
+        }
+        for (_, data) in self.items {
+            bundle.extend_from_slice(&data);
+        }
+        bundle.freeze()
+    }
+}
+
+/// Assembles a data item from its fields, to create items without parsing
+/// them out of a bundle. The signature is not checked.
+#[derive(Debug, Clone)]
+pub struct ItemBuilder {
+    signature_type: SignatureType,
+    signature: Bytes,
+    owner: Bytes,
+    target: Option<[u8; ID_SIZE]>,
+    anchor: Option<[u8; ID_SIZE]>,
+    tags: Vec<Tag>,
+    data: Bytes,
+}
+
+impl ItemBuilder {
+    pub fn new(signature_type: SignatureType, owner: impl Into<Bytes>) -> Self {
+        Self {
+            signature_type,
+            signature: Bytes::new(),
+            owner: owner.into(),
+            target: None,
+            anchor: None,
+            tags: vec![],
+            data: Bytes::new(),
+        }
+    }
+
+    /// Signature of [`ItemBuilder::message`] by the owner.
+    pub fn with_signature(mut self, signature: impl Into<Bytes>) -> Self {
+        self.signature = signature.into();
+        self
+    }
+
+    pub fn with_target(mut self, target: [u8; ID_SIZE]) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn with_anchor(mut self, anchor: [u8; ID_SIZE]) -> Self {
+        self.anchor = Some(anchor);
+        self
+    }
+
+    pub fn with_tags(mut self, tags: Vec<Tag>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn with_data(mut self, data: impl Into<Bytes>) -> Self {
+        self.data = data.into();
+        self
+    }
+
+    /// Message the owner signs, as defined by ANS-104.
+    pub fn message(&self) -> [u8; DEEP_HASH_SIZE] {
+        let tags = Tag::encode_all(&self.tags);
+        signature_data(&self.signed_fields(&tags))
+    }
+
+    /// Serialize the item in the ANS-104 binary format.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let tags = Tag::encode_all(&self.tags);
+        write_item(&self.signed_fields(&tags), self.tags.len() as u64)
+    }
+
+    fn signed_fields<'a>(&'a self, tags: &'a [u8]) -> SignedFields<'a> {
+        SignedFields {
+            signature_type: self.signature_type,
+            signature: &self.signature,
+            owner: &self.owner,
+            target: self.target.as_ref().map(|t| t.as_slice()),
+            anchor: self.anchor.as_ref().map(|a| a.as_slice()),
+            tags,
+            data: &self.data,
+        }
+    }
+
+    /// Build the item as if it was the first item of a bundle.
+    pub fn build(&self) -> Result<BundleItem> {
+        let bytes = self.to_bytes()?;
+        let entry = BundleEntry {
+            id: encode_config(BundleItem::raw_item_id(&bytes)?, URL_SAFE_NO_PAD),
+            offset: 0,
+            size: bytes.len(),
+        };
+        BundleItem::parse(Bytes::from(bytes), &entry, &ParseOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::verify::{SignatureStatus, SignatureVerification};
+    use tokio_stream::StreamExt;
+
+    const FIXTURE: &str = "tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc";
+
+    #[tokio::test]
+    async fn test_build_round_trip() {
+        let data = tokio::fs::read(FIXTURE).await.unwrap();
+        let mut stream = BundleItem::stream(BytesMut::from(data.as_slice())).unwrap();
+        let mut items = vec![];
+        let mut builder = BundleBuilder::new();
+        while let Some(item) = stream.next().await {
+            let item = item.unwrap();
+            builder.push_item(&item).unwrap();
+            items.push(item);
+        }
+
+        let bundle = builder.build();
+
+        assert_eq!(bundle.as_ref(), data.as_slice());
+        let mut stream = BundleItem::stream(BytesMut::from(bundle.as_ref())).unwrap();
+        let mut parsed = vec![];
+        while let Some(item) = stream.next().await {
+            parsed.push(item.unwrap());
+        }
+        assert_eq!(parsed, items);
+    }
+
+    #[tokio::test]
+    async fn test_build_raw_items() {
+        let data = tokio::fs::read(FIXTURE).await.unwrap();
+        let count = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+        let mut offset = BUNDLE_HEADER_SIZE + count * ENTRY_HEADER_SIZE;
+        let mut builder = BundleBuilder::new();
+        for i in 0..count {
+            let entry = BUNDLE_HEADER_SIZE + i * ENTRY_HEADER_SIZE;
+            let size = u32::from_le_bytes(data[entry..entry + 4].try_into().unwrap()) as usize;
+            builder
+                .push_raw_item(data[offset..offset + size].to_vec())
+                .unwrap();
+            offset += size;
+        }
+
+        assert_eq!(builder.len(), count);
+        assert_eq!(builder.build().as_ref(), data.as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_multi_block_tags() {
+        // A block with a positive count, then one with a negative count and
+        // its size in bytes
+        let tags = b"\x02\x02a\x02b\x01\x0a\x02c\x04de\x00";
+        let mut item = vec![];
+        item.extend_from_slice(&1u16.to_le_bytes());
+        item.extend_from_slice(&[7u8; 1024]);
+        item.extend_from_slice(&[0, 0]);
+        item.extend_from_slice(&2u64.to_le_bytes());
+        item.extend_from_slice(&(tags.len() as u64).to_le_bytes());
+        item.extend_from_slice(tags);
+        item.extend_from_slice(b"hello");
+        let mut builder = BundleBuilder::new();
+        builder.push_raw_item(item.clone()).unwrap();
+
+        let bundle = builder.build();
+        let mut stream = BundleItem::stream(BytesMut::from(bundle.as_ref())).unwrap();
+        let parsed = stream.next().await.unwrap().unwrap();
+
+        assert_eq!(parsed.to_bytes().unwrap(), item);
+        // Without the original encoding tags are written as a single block
+        let json = serde_json::to_string(&parsed).unwrap();
+        let deserialized: BundleItem = serde_json::from_str(&json).unwrap();
+        let bytes = deserialized.to_bytes().unwrap();
+        assert_ne!(bytes, item);
+        let mut builder = BundleBuilder::new();
+        builder.push_raw_item(bytes).unwrap();
+        let bundle = builder.build();
+        let mut stream = BundleItem::stream(BytesMut::from(bundle.as_ref())).unwrap();
+        let reparsed = stream.next().await.unwrap().unwrap();
+        assert_eq!(reparsed.tags(), parsed.tags());
+        assert_eq!(reparsed.data(), parsed.data());
+    }
+
+    #[test]
+    fn test_build_empty() {
+        let bundle = BundleBuilder::new().build();
+        assert_eq!(bundle.as_ref(), [0u8; BUNDLE_HEADER_SIZE]);
+    }
+
+    #[tokio::test]
+    async fn test_item_builder() {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+        let builder = ItemBuilder::new(
+            SignatureType::ED25519,
+            key.verifying_key().to_bytes().to_vec(),
+        )
+        .with_target([1u8; ID_SIZE])
+        .with_tags(vec![Tag::new("Content-Type", "text/plain")])
+        .with_data(Bytes::from_static(b"hello"));
+        let signature = ed25519_dalek::Signer::sign(&key, &builder.message());
+        let builder = builder.with_signature(signature.to_bytes().to_vec());
+
+        let item = builder.build().unwrap();
+
+        assert_eq!(
+            item.target(),
+            Some(encode_config([1u8; ID_SIZE], URL_SAFE_NO_PAD).as_str())
+        );
+        assert_eq!(item.anchor(), None);
+        assert_eq!(item.tag("Content-Type"), Some("text/plain"));
+        assert_eq!(item.data().as_ref(), b"hello");
+        assert_eq!(item.to_bytes().unwrap(), builder.to_bytes().unwrap());
+
+        let mut bundle = BundleBuilder::new();
+        bundle.push_item(&item).unwrap();
+        let options = ParseOptions {
+            verify_ids: true,
+            signature_verification: SignatureVerification::Reject,
+            ..Default::default()
+        };
+        let mut stream = BundleItem::stream(BytesMut::from(bundle.build().as_ref()))
+            .unwrap()
+            .with_options(options);
+        let parsed = stream.next().await.unwrap().unwrap();
+        assert_eq!(parsed.id(), item.id());
+        assert_eq!(parsed.signature_status(), Some(SignatureStatus::Valid));
+    }
+
+    #[test]
+    fn test_item_builder_invalid_length() {
+        let result = ItemBuilder::new(SignatureType::ED25519, vec![0u8; 32]).build();
+
+        assert_eq!(
+            result.err(),
+            Some(ParseError::InvalidFieldLength {
+                field: "signature",
+                expected: 64,
+                actual: 0
+            })
+        );
+    }
+}
@@ -1,18 +1,38 @@
+use super::content_type::content_type;
+use super::data::{DataEncoding, ItemData};
+use super::item_ref::BundleItemRef;
 use super::tags::Tag;
+use super::validation::{validate_tags, TagValidation, TagViolation};
+use super::verify::{verify, SignatureStatus, SignatureVerification, SignedFields};
 use crate::errors::ParseError;
-use crate::transaction::tags::TagsReader;
-use base64::{encode_config, URL_SAFE_NO_PAD};
-use bytes::{Buf, BytesMut};
+use crate::transaction::tags::TagsWriter;
+use base64::{decode_config, encode_config, URL_SAFE_NO_PAD};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::pin::Pin;
 use std::task::Poll;
+use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio_stream::Stream;
+use tokio_util::io::{poll_read_buf, StreamReader};
 
 type Result<T> = std::result::Result<T, ParseError>;
 
-#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
-enum SignatureType {
+/// Size of the bundle header holding the number of entries (32 bytes).
+pub(crate) const BUNDLE_HEADER_SIZE: usize = 32;
+/// Size of each entry in the header table: 32 bytes for size and 32 bytes for ID.
+pub(crate) const ENTRY_HEADER_SIZE: usize = 64;
+/// Size of the item IDs and of the optional target and anchor fields.
+pub(crate) const ID_SIZE: usize = 32;
+/// Minimum amount of bytes requested to the reader on each read.
+const MIN_READ_SIZE: usize = 8 * 1024;
+
+/// Signature scheme of a data item, serialized as its ANS-104 numeric value.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(into = "u16", try_from = "u16")]
+pub enum SignatureType {
     Arweave,
     ED25519,
     Ethereum,
@@ -41,8 +61,23 @@ impl TryFrom<u16> for SignatureType {
     }
 }
 
+impl From<SignatureType> for u16 {
+    fn from(value: SignatureType) -> Self {
+        match value {
+            SignatureType::Arweave => 1,
+            SignatureType::ED25519 => 2,
+            SignatureType::Ethereum => 3,
+            SignatureType::Solana => 4,
+            SignatureType::Injectedaptos => 5,
+            SignatureType::Multiaptos => 6,
+            SignatureType::Typedethereum => 7,
+            SignatureType::Starknet => 8,
+        }
+    }
+}
+
 lazy_static! {
-    static ref SIG_CONFIG: HashMap<SignatureType, (usize, usize)> = HashMap::from([
+    pub(crate) static ref SIG_CONFIG: HashMap<SignatureType, (usize, usize)> = HashMap::from([
         (SignatureType::Arweave, (512, 512)),
         (SignatureType::ED25519, (64, 32)),
         (SignatureType::Ethereum, (65, 65)),
@@ -54,86 +89,666 @@ lazy_static! {
     ]);
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+/// Optional checks performed while parsing each item of a bundle.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ParseOptions {
+    /// Check that the ID in the header table is the SHA-256 of the item signature.
+    pub verify_ids: bool,
+    /// Check the signature of each item, recording its status or rejecting
+    /// invalid ones.
+    pub signature_verification: SignatureVerification,
+    /// Check the tags of each item against the ANS-104 limits: at most 128
+    /// tags, names up to 1024 bytes, values up to 3072 bytes and neither of
+    /// them empty. Lenient validation records the violations in
+    /// [`BundleItem::tag_violations`], strict validation rejects the item.
+    pub tag_validation: TagValidation,
+    /// Leave the data out of the items, only recording its size and offset.
+    /// The data of nested bundles is kept so they can be indexed. Each item is
+    /// still read whole to hash its data, so this saves the memory of the
+    /// parsed items but not the time to download them.
+    pub headers_only: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct BundleItem {
     id: String,
+    signature_type: SignatureType,
     signature: String,
     owner: String,
+    /// Wallet address of the owner, see [`super::address::owner_address`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner_address: Option<String>,
     target: Option<String>,
     anchor: Option<String>,
     tags: Vec<Tag>,
-    data: String,
+    #[serde(skip)]
+    encoded_tags: EncodedTags,
+    /// Position of the item within its bundle.
+    #[serde(default)]
+    offset_in_bundle: usize,
+    #[serde(default)]
+    size: usize,
+    #[serde(
+        default = "ItemData::omitted",
+        skip_serializing_if = "ItemData::is_omitted"
+    )]
+    data: ItemData,
+    /// Size of the data, set when parsing headers only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data_size: Option<usize>,
+    /// Position of the data within the bundle, set when parsing headers only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data_offset: Option<usize>,
+    /// Base64url SHA-256 of the data.
+    #[serde(default)]
+    data_sha256: String,
+    /// Content type from the `Content-Type` tag or sniffed from the data.
+    #[serde(default)]
+    content_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature_status: Option<SignatureStatus>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tag_violations: Vec<TagViolation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_id: Option<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    depth: usize,
+    /// Why the data could not be indexed as a nested bundle although the item
+    /// is tagged as one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nested_bundle_error: Option<String>,
+    /// Gateway the bundle was downloaded from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gateway: Option<String>,
+}
+
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
+/// Tags exactly as they are encoded in the item, which the signature covers.
+/// Ignored when comparing items since the same tags have several encodings.
+#[derive(Debug, Default, Clone)]
+struct EncodedTags(Option<Bytes>);
+
+impl PartialEq for EncodedTags {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
 }
 
 impl BundleItem {
-    fn parse_item(mut data: BytesMut, id: String) -> Result<Self> {
-        // Read signature type (2 bytes)
-        let signature_type: SignatureType = data.get_u16_le().try_into()?;
-
-        let (sig_size, owner_size) = SIG_CONFIG[&signature_type];
-        let signature = encode_config(data.split_to(sig_size), URL_SAFE_NO_PAD);
-        let owner = encode_config(data.split_to(owner_size), URL_SAFE_NO_PAD);
-
-        let target = Self::read_optional_string(&mut data)?;
-        let anchor = Self::read_optional_string(&mut data)?;
-
-        let tags_length = data.get_u64_le();
-        let num_bytes_for_tags = data.get_u64_le();
-        let tags = if tags_length > 0 && num_bytes_for_tags > 0 {
-            let tags_bytes = data.split_to(num_bytes_for_tags as usize);
-            let tags = TagsReader::deserialize(&tags_bytes)?;
-            if tags.len() != tags_length as usize {
-                return Err(ParseError::InvalidTagsLength(tags_length, tags.len()));
+    fn parse_item(data: Bytes, id: &[u8], offset: usize, options: &ParseOptions) -> Result<Self> {
+        let item = BundleItemRef::parse(&data, id, offset)?;
+        Self::from_ref(&item, options, |slice| data.slice_ref(slice))
+    }
+
+    /// Build an owned item from its view, `to_bytes` giving the data and the
+    /// encoded tags of the item from their slices.
+    pub(crate) fn from_ref(
+        item: &BundleItemRef,
+        options: &ParseOptions,
+        to_bytes: impl Fn(&[u8]) -> Bytes,
+    ) -> Result<Self> {
+        let id = item.id();
+        if options.verify_ids {
+            Self::verify_id(&id, item.signature())?;
+        }
+        let tags = item.tags()?;
+        let content_type = content_type(&tags, item.data());
+
+        let tag_violations = match options.tag_validation {
+            TagValidation::Disabled => vec![],
+            TagValidation::Lenient => validate_tags(&tags),
+            TagValidation::Strict => {
+                let violations = validate_tags(&tags);
+                if !violations.is_empty() {
+                    return Err(ParseError::TagViolations(violations));
+                }
+                violations
             }
-            tags
-        } else {
-            vec![]
         };
 
-        let data = encode_config(data, URL_SAFE_NO_PAD);
+        let signature_status = match options.signature_verification {
+            SignatureVerification::Disabled => None,
+            SignatureVerification::Record | SignatureVerification::Reject => {
+                Some(verify(&item.signed_fields()))
+            }
+        };
+        if options.signature_verification == SignatureVerification::Reject
+            && signature_status == Some(SignatureStatus::Invalid)
+        {
+            return Err(ParseError::InvalidSignature(id));
+        }
+
+        let encode = |bytes: &[u8]| encode_config(bytes, URL_SAFE_NO_PAD);
 
-        Ok(BundleItem {
+        let mut bundle_item = BundleItem {
             id,
-            signature,
-            owner,
-            target,
-            anchor,
+            signature_type: item.signature_type(),
+            signature: encode(item.signature()),
+            owner: encode(item.owner()),
+            owner_address: item.owner_address(),
+            target: item.target().map(encode),
+            anchor: item.anchor().map(encode),
             tags,
-            data,
-        })
+            encoded_tags: EncodedTags(Some(to_bytes(item.raw_tags()))),
+            offset_in_bundle: item.offset(),
+            size: item.size(),
+            data: ItemData::default(),
+            data_size: None,
+            data_offset: None,
+            data_sha256: encode(&Sha256::digest(item.data())),
+            content_type,
+            signature_status,
+            tag_violations,
+            parent_id: None,
+            depth: 0,
+            nested_bundle_error: None,
+            gateway: None,
+        };
+        if options.headers_only {
+            bundle_item.data_size = Some(item.data().len());
+            bundle_item.data_offset = Some(item.data_offset());
+            let data = if bundle_item.is_bundle() {
+                to_bytes(item.data())
+            } else {
+                // Tags are copied so that the item does not keep its data alive
+                let tags = Bytes::copy_from_slice(item.raw_tags());
+                bundle_item.encoded_tags = EncodedTags(Some(tags));
+                Bytes::new()
+            };
+            bundle_item.data = ItemData::skipped(data);
+        } else {
+            bundle_item.data = to_bytes(item.data()).into();
+        }
+        Ok(bundle_item)
     }
 
-    pub fn stream(mut data: BytesMut) -> Result<BundleStream> {
-        let num_entries = data.get_u32_le();
-        data.advance(28);
+    /// Parse the header table of a bundle that is already fully loaded in memory.
+    pub fn stream(data: BytesMut) -> Result<BundleStream> {
+        let mut data = ItemReader { data, offset: 0 };
+        let num_entries = read_header_int(
+            &data.read_bytes(BUNDLE_HEADER_SIZE, "entries count")?,
+            "entries count",
+            0,
+        )?;
         let mut entries = vec![];
         for _ in 0..num_entries {
-            let size_entry = data.get_u32_le();
-            data.advance(28);
-            let id = encode_config(data.split_to(32), URL_SAFE_NO_PAD);
-            entries.push((size_entry, id));
+            let offset = data.offset;
+            entries.push(Self::read_entry(
+                &data.read_bytes(ENTRY_HEADER_SIZE, "entry header")?,
+                offset,
+            )?);
+        }
+
+        Ok(BundleStream {
+            reader: Box::pin(tokio::io::empty()),
+            offset: data.offset,
+            bytes: data.data,
+            entries,
+            current_entry: 0,
+            options: ParseOptions::default(),
+            parent_id: None,
+            depth: 0,
+            gateway: None,
+            last_entry: None,
+            last_item: None,
+            interrupted: false,
+        })
+    }
+
+    /// Parse the header table incrementally from any [`AsyncRead`]. Items are
+    /// read lazily while the returned stream is polled, so only the item being
+    /// parsed is kept in memory.
+    pub async fn stream_reader<R>(reader: R) -> Result<BundleStream>
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        let mut reader: Pin<Box<dyn AsyncRead + Send>> = Box::pin(reader);
+        let mut header = [0u8; BUNDLE_HEADER_SIZE];
+        Self::read_header(&mut reader, &mut header, "entries count", 0).await?;
+        let num_entries = read_header_int(&header, "entries count", 0)?;
+
+        let mut entries = vec![];
+        let mut entry = [0u8; ENTRY_HEADER_SIZE];
+        for i in 0..num_entries as usize {
+            let offset = BUNDLE_HEADER_SIZE + i * ENTRY_HEADER_SIZE;
+            Self::read_header(&mut reader, &mut entry, "entry header", offset).await?;
+            entries.push(Self::read_entry(&entry, offset)?);
         }
 
         Ok(BundleStream {
-            bytes: data,
+            reader,
+            offset: BUNDLE_HEADER_SIZE + entries.len() * ENTRY_HEADER_SIZE,
+            bytes: BytesMut::new(),
             entries,
             current_entry: 0,
+            options: ParseOptions::default(),
+            parent_id: None,
+            depth: 0,
+            gateway: None,
+            last_entry: None,
+            last_item: None,
+            interrupted: false,
         })
     }
 
-    fn read_optional_string(data: &mut BytesMut) -> Result<Option<String>> {
-        match data.get_u8() {
-            0 => Ok(None),
-            1 => Ok(Some(encode_config(data.split_to(32), URL_SAFE_NO_PAD))),
-            _ => Err(ParseError::InvalidPresenceByte(data.get_u8())),
+    /// Same as [`BundleItem::stream_reader`] but over a [`Stream`] of chunks,
+    /// like the body of an HTTP response.
+    pub async fn stream_chunks<S, E>(chunks: S) -> Result<BundleStream>
+    where
+        S: Stream<Item = std::result::Result<Bytes, E>> + Send + 'static,
+        E: Into<std::io::Error>,
+    {
+        Self::stream_reader(StreamReader::new(chunks)).await
+    }
+
+    async fn read_header(
+        reader: &mut Pin<Box<dyn AsyncRead + Send>>,
+        buffer: &mut [u8],
+        field: &'static str,
+        offset: usize,
+    ) -> Result<()> {
+        match reader.read_exact(buffer).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                Err(ParseError::UnexpectedEof { field, offset })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Serialize the item in the ANS-104 binary format, so that parsing the
+    /// result gives back the same item. Tags are written as they were encoded
+    /// in the parsed item, or encoded with [`TagsWriter`] for other items.
+    /// Items whose data was left out cannot be serialized.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.data.is_skipped() && self.data_size != Some(self.data.len()) {
+            return Err(ParseError::MissingField("data"));
+        }
+        let decode = |value: &str| decode_config(value, URL_SAFE_NO_PAD);
+        let signature = decode(&self.signature)?;
+        let owner = decode(&self.owner)?;
+        let target = self.target.as_deref().map(decode).transpose()?;
+        let anchor = self.anchor.as_deref().map(decode).transpose()?;
+        let tags = match &self.encoded_tags.0 {
+            Some(tags) => tags.clone(),
+            None => Bytes::from(TagsWriter::serialize(&self.tags)),
+        };
+        let fields = SignedFields {
+            signature_type: self.signature_type,
+            signature: &signature,
+            owner: &owner,
+            target: target.as_deref(),
+            anchor: anchor.as_deref(),
+            tags: &tags,
+            data: &self.data,
+        };
+        write_item(&fields, self.tags.len() as u64)
+    }
+
+    pub(crate) fn decoded_id(&self) -> Result<[u8; ID_SIZE]> {
+        let id = decode_config(&self.id, URL_SAFE_NO_PAD)?;
+        id.as_slice()
+            .try_into()
+            .map_err(|_| ParseError::InvalidFieldLength {
+                field: "id",
+                expected: ID_SIZE,
+                actual: id.len(),
+            })
+    }
+
+    /// Compute the ID of an item in binary format from its signature.
+    pub(crate) fn raw_item_id(data: &[u8]) -> Result<[u8; ID_SIZE]> {
+        let mut data = ItemReader { data, offset: 0 };
+        let signature_type: SignatureType = data.read_u16_le("signature type")?.try_into()?;
+        let (sig_size, _) = SIG_CONFIG[&signature_type];
+        let signature = data.read_bytes(sig_size, "signature")?;
+        Ok(Sha256::digest(signature).into())
+    }
+
+    /// Check the tags of the item against the ANS-104 limits.
+    pub fn validate_tags(&self) -> Vec<TagViolation> {
+        validate_tags(&self.tags)
+    }
+
+    /// Whether the data of this item is itself an ANS-104 bundle.
+    pub fn is_bundle(&self) -> bool {
+        self.tag("Bundle-Format") == Some("binary") && self.tag("Bundle-Version") == Some("2.0.0")
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn signature_type(&self) -> SignatureType {
+        self.signature_type
+    }
+
+    /// Base64url signature.
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    /// Base64url public key of the signer.
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    pub fn owner_address(&self) -> Option<&str> {
+        self.owner_address.as_deref()
+    }
+
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    pub fn anchor(&self) -> Option<&str> {
+        self.anchor.as_deref()
+    }
+
+    pub fn tags(&self) -> &[Tag] {
+        &self.tags
+    }
+
+    /// Value of the first tag named `name`.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|t| t.name() == name).map(Tag::value)
+    }
+
+    /// Values of all the tags named `name`, in order.
+    pub fn tag_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.tags
+            .iter()
+            .filter(move |t| t.name() == name)
+            .map(Tag::value)
+    }
+
+    /// Position of the item within its bundle, which is the nested bundle for
+    /// items with a parent.
+    pub fn offset_in_bundle(&self) -> usize {
+        self.offset_in_bundle
+    }
+
+    /// Size of the whole item in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn data(&self) -> &Bytes {
+        self.data.bytes()
+    }
+
+    /// Set when the item was parsed with signature verification enabled.
+    pub fn signature_status(&self) -> Option<SignatureStatus> {
+        self.signature_status
+    }
+
+    /// Set when the item was parsed with lenient tag validation.
+    pub fn tag_violations(&self) -> &[TagViolation] {
+        &self.tag_violations
+    }
+
+    /// Whether the data of the item was parsed, see [`ParseOptions::headers_only`].
+    pub fn has_data(&self) -> bool {
+        !self.data.is_skipped()
+    }
+
+    pub fn data_size(&self) -> Option<usize> {
+        self.data_size
+    }
+
+    pub fn data_offset(&self) -> Option<usize> {
+        self.data_offset
+    }
+
+    pub fn data_sha256(&self) -> &str {
+        &self.data_sha256
+    }
+
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    /// Choose how the data is written when the item is serialized. Data of
+    /// items parsed headers only is never written.
+    pub fn set_data_encoding(&mut self, encoding: DataEncoding) {
+        self.data.set_encoding(encoding);
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// ID of the item containing this one when it comes from a nested bundle.
+    pub fn parent_id(&self) -> Option<&str> {
+        self.parent_id.as_deref()
+    }
+
+    pub fn gateway(&self) -> Option<&str> {
+        self.gateway.as_deref()
+    }
+
+    pub fn nested_bundle_error(&self) -> Option<&str> {
+        self.nested_bundle_error.as_deref()
+    }
+
+    pub(crate) fn set_nested_bundle_error(&mut self, error: Option<String>) {
+        self.nested_bundle_error = error;
+    }
+
+    pub(crate) fn set_parent_id(&mut self, parent_id: Option<String>) {
+        self.parent_id = parent_id;
+    }
+
+    /// Parse a single item in binary format, as found at `entry` in its bundle.
+    pub fn parse(data: Bytes, entry: &BundleEntry, options: &ParseOptions) -> Result<Self> {
+        let id = decode_config(&entry.id, URL_SAFE_NO_PAD)?;
+        Self::parse_item(data, &id, entry.offset, options)
+    }
+
+    /// Parse the data of this item as a nested bundle. Items of the nested bundle
+    /// reference this item as their parent and are one level deeper. The data
+    /// must hold all the items of the header table.
+    pub fn stream_nested(&self) -> Result<BundleStream> {
+        let mut stream = Self::stream(BytesMut::from(self.data.bytes().clone()))?;
+        let items_size = stream.entries.iter().map(|(size, _)| *size as usize).sum();
+        if stream.bytes.len() < items_size {
+            return Err(ParseError::UnexpectedEof {
+                field: "item",
+                offset: stream.offset + stream.bytes.len(),
+            });
+        }
+        stream.parent_id = Some(self.id.clone());
+        stream.depth = self.depth + 1;
+        stream.gateway = self.gateway.clone();
+        Ok(stream)
+    }
+
+    fn verify_id(id: &str, signature: &[u8]) -> Result<()> {
+        let computed_id = encode_config(Sha256::digest(signature), URL_SAFE_NO_PAD);
+        if computed_id != id {
+            return Err(ParseError::IdMismatch {
+                header_id: id.to_string(),
+                computed_id,
+            });
         }
+        Ok(())
+    }
+
+    fn read_entry(entry: &[u8], offset: usize) -> Result<(u32, [u8; ID_SIZE])> {
+        let size_entry = read_header_int(entry, "entry size", offset)?;
+        let mut id = [0u8; ID_SIZE];
+        id.copy_from_slice(&entry[ENTRY_HEADER_SIZE - ID_SIZE..ENTRY_HEADER_SIZE]);
+        Ok((size_entry, id))
     }
 }
 
+/// Serialize an item in the ANS-104 binary format, `tags_count` being the
+/// number of tags encoded in `fields.tags`.
+pub(crate) fn write_item(fields: &SignedFields, tags_count: u64) -> Result<Vec<u8>> {
+    let (sig_size, owner_size) = SIG_CONFIG[&fields.signature_type];
+    let check_length = |field: &'static str, value: &[u8], expected: usize| {
+        if value.len() != expected {
+            return Err(ParseError::InvalidFieldLength {
+                field,
+                expected,
+                actual: value.len(),
+            });
+        }
+        Ok(())
+    };
+    check_length("signature", fields.signature, sig_size)?;
+    check_length("owner", fields.owner, owner_size)?;
+
+    let mut bytes = Vec::with_capacity(
+        2 + sig_size + owner_size + 2 * (1 + ID_SIZE) + 16 + fields.tags.len() + fields.data.len(),
+    );
+    bytes.put_u16_le(fields.signature_type.into());
+    bytes.extend_from_slice(fields.signature);
+    bytes.extend_from_slice(fields.owner);
+    for (field, value) in [("target", fields.target), ("anchor", fields.anchor)] {
+        match value {
+            Some(value) => {
+                check_length(field, value, ID_SIZE)?;
+                bytes.put_u8(1);
+                bytes.extend_from_slice(value);
+            }
+            None => bytes.put_u8(0),
+        }
+    }
+    bytes.put_u64_le(tags_count);
+    bytes.put_u64_le(fields.tags.len() as u64);
+    bytes.extend_from_slice(fields.tags);
+    bytes.extend_from_slice(fields.data);
+    Ok(bytes)
+}
+
+/// Read a 256 bit little endian integer of the header table. Values that do
+/// not fit in 32 bits are rejected rather than truncated.
+pub(crate) fn read_header_int(bytes: &[u8], field: &'static str, offset: usize) -> Result<u32> {
+    let (mut value, upper) = bytes[..BUNDLE_HEADER_SIZE].split_at(4);
+    if upper.iter().any(|b| *b != 0) {
+        return Err(ParseError::HeaderOverflow { field, offset });
+    }
+    Ok(value.get_u32_le())
+}
+
+/// Bounds-checked reader over a buffer that keeps track of the offset within
+/// the bundle, so truncated data is reported instead of panicking.
+pub(crate) struct ItemReader<B> {
+    pub data: B,
+    pub offset: usize,
+}
+
+impl<B: Buf> ItemReader<B> {
+    fn ensure(&self, len: usize, field: &'static str) -> Result<()> {
+        if self.data.remaining() < len {
+            return Err(ParseError::UnexpectedEof {
+                field,
+                offset: self.offset,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn read_u8(&mut self, field: &'static str) -> Result<u8> {
+        self.ensure(1, field)?;
+        self.offset += 1;
+        Ok(self.data.get_u8())
+    }
+
+    pub fn read_u16_le(&mut self, field: &'static str) -> Result<u16> {
+        self.ensure(2, field)?;
+        self.offset += 2;
+        Ok(self.data.get_u16_le())
+    }
+
+    pub fn read_u64_le(&mut self, field: &'static str) -> Result<u64> {
+        self.ensure(8, field)?;
+        self.offset += 8;
+        Ok(self.data.get_u64_le())
+    }
+
+    fn read_bytes(&mut self, len: usize, field: &'static str) -> Result<Bytes> {
+        self.ensure(len, field)?;
+        self.offset += len;
+        Ok(self.data.copy_to_bytes(len))
+    }
+}
+
+impl<'a> ItemReader<&'a [u8]> {
+    pub fn read_slice(&mut self, len: usize, field: &'static str) -> Result<&'a [u8]> {
+        self.ensure(len, field)?;
+        self.offset += len;
+        let (slice, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(slice)
+    }
+}
+
+/// Position of an item within its bundle, as described by the header table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleEntry {
+    pub id: String,
+    pub offset: usize,
+    pub size: usize,
+}
+
 pub struct BundleStream {
+    reader: Pin<Box<dyn AsyncRead + Send>>,
+    offset: usize,
     bytes: BytesMut,
-    entries: Vec<(u32, String)>,
+    entries: Vec<(u32, [u8; ID_SIZE])>,
     current_entry: usize,
+    options: ParseOptions,
+    parent_id: Option<String>,
+    depth: usize,
+    gateway: Option<String>,
+    last_entry: Option<BundleEntry>,
+    last_item: Option<Bytes>,
+    interrupted: bool,
+}
+
+impl BundleStream {
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Entry of the last item yielded by the stream, useful to locate items
+    /// that failed to parse.
+    pub fn last_entry(&self) -> Option<&BundleEntry> {
+        self.last_entry.as_ref()
+    }
+
+    /// Raw bytes of the last item yielded by the stream when it failed to
+    /// parse, truncated if the bundle ended before the end of the item.
+    pub fn last_item(&self) -> Option<&Bytes> {
+        self.last_item.as_ref()
+    }
+
+    /// Whether reading the bundle failed, leaving the remaining entries
+    /// unread. Errors of the items read so far do not interrupt the stream.
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    /// ID of the item containing this bundle when it is nested.
+    pub fn parent_id(&self) -> Option<&str> {
+        self.parent_id.as_deref()
+    }
+
+    /// Record the gateway serving the bundle on each of its items.
+    pub fn with_gateway(mut self, gateway: String) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    pub fn gateway(&self) -> Option<&str> {
+        self.gateway.as_deref()
+    }
 }
 
 impl Stream for BundleStream {
@@ -141,13 +756,53 @@ impl Stream for BundleStream {
 
     fn poll_next(
         mut self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
+        cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
         let this = self.as_mut().get_mut();
         if this.current_entry < this.entries.len() {
-            let (size, ref id) = this.entries[this.current_entry];
-            let item_data = this.bytes.split_to(size as usize);
-            let bundle = BundleItem::parse_item(item_data, id.to_string());
+            let (size, id) = this.entries[this.current_entry];
+            let size = size as usize;
+            this.last_entry = Some(BundleEntry {
+                id: encode_config(id, URL_SAFE_NO_PAD),
+                offset: this.offset,
+                size,
+            });
+            while this.bytes.len() < size {
+                // Grow with the data received rather than with the size
+                // announced in the header table
+                this.bytes.reserve(MIN_READ_SIZE);
+                match poll_read_buf(this.reader.as_mut(), cx, &mut this.bytes) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Ok(0)) => {
+                        this.current_entry = this.entries.len();
+                        this.interrupted = true;
+                        let offset = this.offset + this.bytes.len();
+                        this.last_item = Some(this.bytes.split().freeze());
+                        return Poll::Ready(Some(Err(ParseError::UnexpectedEof {
+                            field: "item",
+                            offset,
+                        })));
+                    }
+                    Poll::Ready(Ok(_)) => continue,
+                    Poll::Ready(Err(e)) => {
+                        this.current_entry = this.entries.len();
+                        this.interrupted = true;
+                        return Poll::Ready(Some(Err(e.into())));
+                    }
+                }
+            }
+            let item_data = this.bytes.split_to(size).freeze();
+            let bundle = BundleItem::parse_item(item_data.clone(), &id, this.offset, &this.options)
+                .map(|mut item| {
+                    item.parent_id = this.parent_id.clone();
+                    item.depth = this.depth;
+                    item.gateway = this.gateway.clone();
+                    item
+                });
+            // Only failed items are kept, so that the buffer of parsed items is
+            // released as soon as they are dropped
+            this.last_item = bundle.is_err().then_some(item_data);
+            this.offset += size;
             this.current_entry += 1;
             return Poll::Ready(Some(bundle));
         }
@@ -160,6 +815,7 @@ mod tests {
     use std::vec;
 
     use super::*;
+    use crate::transaction::builder::BundleBuilder;
     use bytes::BytesMut;
     use tokio::io::AsyncBufReadExt;
     use tokio_stream::StreamExt;
@@ -193,4 +849,478 @@ mod tests {
         }
         assert_eq!(parsed, expected);
     }
+
+    #[test]
+    fn test_deserialize_older_items() {
+        let expected = std::fs::read_to_string(
+            "tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc.expected",
+        )
+        .unwrap();
+        let mut json: serde_json::Value =
+            serde_json::from_str(expected.lines().next().unwrap()).unwrap();
+        // Fields added after items were first stored
+        for field in [
+            "offset_in_bundle",
+            "size",
+            "owner_address",
+            "data_sha256",
+            "content_type",
+        ] {
+            json.as_object_mut().unwrap().remove(field);
+        }
+
+        let item: BundleItem = serde_json::from_value(json).unwrap();
+
+        assert_eq!(item.id(), "pt1mPlK8P8ryG5HaWOlbklO3ikOlo1h_kPrzAsQ61iA");
+        assert_eq!(item.offset_in_bundle(), 0);
+        assert_eq!(item.size(), 0);
+        assert_eq!(item.owner_address(), None);
+        assert_eq!(item.data_sha256(), "");
+        assert_eq!(item.content_type(), "");
+    }
+
+    #[tokio::test]
+    async fn test_item_accessors() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let mut stream = BundleItem::stream(BytesMut::from(data.as_slice())).unwrap();
+        let mut item = stream.next().await.unwrap().unwrap();
+        item.tags.push(Tag::new("Type", "other"));
+
+        assert_eq!(item.id(), "pt1mPlK8P8ryG5HaWOlbklO3ikOlo1h_kPrzAsQ61iA");
+        assert_eq!(item.signature_type(), SignatureType::Ethereum);
+        assert_eq!(
+            item.offset_in_bundle(),
+            BUNDLE_HEADER_SIZE + 71 * ENTRY_HEADER_SIZE
+        );
+        assert_eq!(
+            item.size(),
+            u32::from_le_bytes(data[32..36].try_into().unwrap()) as usize
+        );
+        assert_eq!(
+            item.owner_address(),
+            Some("0x6A179C931edB91FAE1dBAc5D40bA95758c096128")
+        );
+        assert_eq!(item.target(), None);
+        assert_eq!(item.anchor(), None);
+        assert_eq!(item.tags()[1].name(), "Content-Type");
+        assert_eq!(
+            item.tag("Content-Type"),
+            Some("application/x.arweave-manifest+json")
+        );
+        assert_eq!(item.tag("Missing"), None);
+        assert_eq!(
+            item.tag_values("Type").collect::<Vec<_>>(),
+            vec!["manifest", "other"]
+        );
+        assert!(item.data().starts_with(b"{\"manifest\":\"arweave/paths\""));
+        assert_eq!(item.signature_status(), None);
+        assert!(item.tag_violations().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stream_chunks() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let mut expected = BundleItem::stream(BytesMut::from(data.as_slice())).unwrap();
+        let chunks = data
+            .chunks(1000)
+            .map(|c| Ok::<_, std::io::Error>(Bytes::copy_from_slice(c)))
+            .collect::<Vec<_>>();
+
+        let result = BundleItem::stream_chunks(tokio_stream::iter(chunks)).await;
+
+        assert!(result.is_ok());
+        let mut stream = result.unwrap();
+        let mut count = 0;
+        while let Some(item) = stream.next().await {
+            assert_eq!(item.unwrap(), expected.next().await.unwrap().unwrap());
+            count += 1;
+        }
+        assert_eq!(count, 71);
+    }
+
+    #[tokio::test]
+    async fn test_stream_reader_truncated() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let truncated = &data[..data.len() - 10];
+
+        let mut stream = BundleItem::stream_reader(std::io::Cursor::new(truncated.to_vec()))
+            .await
+            .unwrap();
+        let mut results = vec![];
+        while let Some(item) = stream.next().await {
+            results.push(item);
+        }
+
+        assert_eq!(results.len(), 71);
+        assert!(results[..70].iter().all(|r| r.is_ok()));
+        assert_eq!(
+            results[70],
+            Err(ParseError::UnexpectedEof {
+                field: "item",
+                offset: truncated.len()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_item_truncated() {
+        let data =
+            std::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc").unwrap();
+        let offset = BUNDLE_HEADER_SIZE + 71 * ENTRY_HEADER_SIZE;
+        // (truncated length, field being read, offset of that field)
+        let expected_fields = [
+            (0, "signature type", 0),
+            (1, "signature type", 0),
+            (2, "signature", 2),
+            (66, "signature", 2),
+            (67, "owner", 67),
+            (132, "target", 132),
+            (133, "anchor", 133),
+            (134, "tags count", 134),
+            (142, "tags size", 142),
+            (150, "tags", 150),
+        ];
+        for (len, field, field_offset) in expected_fields {
+            let item = Bytes::copy_from_slice(&data[offset..offset + len]);
+            let result =
+                BundleItem::parse_item(item, &[0u8; ID_SIZE], offset, &ParseOptions::default());
+            assert_eq!(
+                result,
+                Err(ParseError::UnexpectedEof {
+                    field,
+                    offset: offset + field_offset
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_stream_truncated_header() {
+        let data =
+            std::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc").unwrap();
+
+        let result = BundleItem::stream(BytesMut::from(&data[..10]));
+        assert_eq!(
+            result.err(),
+            Some(ParseError::UnexpectedEof {
+                field: "entries count",
+                offset: 0
+            })
+        );
+
+        let result = BundleItem::stream(BytesMut::from(&data[..100]));
+        assert_eq!(
+            result.err(),
+            Some(ParseError::UnexpectedEof {
+                field: "entry header",
+                offset: 96
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_header_overflow() {
+        let data =
+            std::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc").unwrap();
+
+        let mut count = data.clone();
+        count[4] = 1;
+        let result = BundleItem::stream(BytesMut::from(count.as_slice()));
+        assert_eq!(
+            result.err(),
+            Some(ParseError::HeaderOverflow {
+                field: "entries count",
+                offset: 0
+            })
+        );
+
+        let mut size = data.clone();
+        size[BUNDLE_HEADER_SIZE + ENTRY_HEADER_SIZE + 31] = 1;
+        let result = BundleItem::stream_reader(std::io::Cursor::new(size)).await;
+        assert_eq!(
+            result.err(),
+            Some(ParseError::HeaderOverflow {
+                field: "entry size",
+                offset: BUNDLE_HEADER_SIZE + ENTRY_HEADER_SIZE
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_oversized_entry() {
+        let mut bundle = vec![0u8; BUNDLE_HEADER_SIZE + ENTRY_HEADER_SIZE];
+        bundle[0] = 1;
+        bundle[BUNDLE_HEADER_SIZE..BUNDLE_HEADER_SIZE + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        bundle.extend_from_slice(b"short item");
+
+        let mut stream = BundleItem::stream_reader(std::io::Cursor::new(bundle))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            stream.next().await,
+            Some(Err(ParseError::UnexpectedEof {
+                field: "item",
+                offset: BUNDLE_HEADER_SIZE + ENTRY_HEADER_SIZE + 10
+            }))
+        );
+        assert!(stream.bytes.capacity() <= 4 * MIN_READ_SIZE);
+    }
+
+    #[test]
+    fn test_parse_item_invalid_presence_byte() {
+        let mut item = BytesMut::new();
+        item.extend_from_slice(&2u16.to_le_bytes());
+        item.extend_from_slice(&[0u8; 96]);
+        item.extend_from_slice(&[7u8]);
+
+        let result =
+            BundleItem::parse_item(item.freeze(), &[0u8; ID_SIZE], 0, &ParseOptions::default());
+
+        assert_eq!(result, Err(ParseError::InvalidPresenceByte(7)));
+    }
+
+    #[tokio::test]
+    async fn test_stream_verify_ids() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let options = ParseOptions {
+            verify_ids: true,
+            ..Default::default()
+        };
+        let mut stream = BundleItem::stream(BytesMut::from(data.as_slice()))
+            .unwrap()
+            .with_options(options);
+
+        let mut count = 0;
+        while let Some(item) = stream.next().await {
+            assert!(item.is_ok());
+            count += 1;
+        }
+        assert_eq!(count, 71);
+    }
+
+    #[tokio::test]
+    async fn test_stream_verify_ids_mismatch() {
+        let mut data =
+            tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+                .await
+                .unwrap();
+        // Flip a byte of the signature of the first item
+        let offset = BUNDLE_HEADER_SIZE + 71 * ENTRY_HEADER_SIZE;
+        data[offset + 10] ^= 0xff;
+        let options = ParseOptions {
+            verify_ids: true,
+            ..Default::default()
+        };
+        let mut stream = BundleItem::stream(BytesMut::from(data.as_slice()))
+            .unwrap()
+            .with_options(options);
+
+        let first = stream.next().await.unwrap();
+        assert!(matches!(
+            first,
+            Err(ParseError::IdMismatch { ref header_id, ref computed_id })
+                if header_id == "pt1mPlK8P8ryG5HaWOlbklO3ikOlo1h_kPrzAsQ61iA" && computed_id != header_id
+        ));
+        assert!(stream.next().await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stream_verify_signatures() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let options = ParseOptions {
+            signature_verification: SignatureVerification::Record,
+            ..Default::default()
+        };
+        let mut stream = BundleItem::stream(BytesMut::from(data.as_slice()))
+            .unwrap()
+            .with_options(options);
+
+        let mut count = 0;
+        while let Some(item) = stream.next().await {
+            assert_eq!(item.unwrap().signature_status, Some(SignatureStatus::Valid));
+            count += 1;
+        }
+        assert_eq!(count, 71);
+    }
+
+    #[tokio::test]
+    async fn test_stream_verify_signatures_tampered() {
+        let mut data =
+            tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+                .await
+                .unwrap();
+        // Flip the last byte of the data of the first item
+        let first_size = u32::from_le_bytes(data[32..36].try_into().unwrap()) as usize;
+        let offset = BUNDLE_HEADER_SIZE + 71 * ENTRY_HEADER_SIZE;
+        data[offset + first_size - 1] ^= 0xff;
+
+        for (mode, expected) in [
+            (
+                SignatureVerification::Record,
+                Ok(Some(SignatureStatus::Invalid)),
+            ),
+            (
+                SignatureVerification::Reject,
+                Err(ParseError::InvalidSignature(
+                    "pt1mPlK8P8ryG5HaWOlbklO3ikOlo1h_kPrzAsQ61iA".to_string(),
+                )),
+            ),
+        ] {
+            let options = ParseOptions {
+                signature_verification: mode,
+                ..Default::default()
+            };
+            let mut stream = BundleItem::stream(BytesMut::from(data.as_slice()))
+                .unwrap()
+                .with_options(options);
+
+            let first = stream.next().await.unwrap();
+            assert_eq!(first.map(|i| i.signature_status), expected);
+            let second = stream.next().await.unwrap().unwrap();
+            assert_eq!(second.signature_status, Some(SignatureStatus::Valid));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_headers_only() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let mut expected = BundleItem::stream(BytesMut::from(data.as_slice())).unwrap();
+        let options = ParseOptions {
+            headers_only: true,
+            ..Default::default()
+        };
+        let mut stream = BundleItem::stream(BytesMut::from(data.as_slice()))
+            .unwrap()
+            .with_options(options);
+
+        let mut count = 0;
+        while let Some(item) = stream.next().await {
+            let mut item = item.unwrap();
+            let expected = expected.next().await.unwrap().unwrap();
+            let data_offset = item.data_offset().unwrap();
+            assert!(!item.has_data());
+            assert!(item.data().is_empty());
+            assert_eq!(item.data_size(), Some(expected.data().len()));
+            assert_eq!(
+                &data[data_offset..data_offset + expected.data().len()],
+                expected.data()
+            );
+            assert_eq!(
+                item.data_sha256(),
+                encode_config(Sha256::digest(expected.data()), URL_SAFE_NO_PAD)
+            );
+            assert_eq!(item.content_type(), expected.content_type());
+            if expected.data().is_empty() {
+                assert_eq!(item.to_bytes(), expected.to_bytes());
+            } else {
+                assert_eq!(item.to_bytes(), Err(ParseError::MissingField("data")));
+            }
+            item.set_data_encoding(DataEncoding::Base64);
+            let json = serde_json::to_value(&item).unwrap();
+            assert!(json.get("data").is_none());
+            assert_eq!(json["id"], expected.id());
+            count += 1;
+        }
+        assert_eq!(count, 71);
+    }
+
+    #[tokio::test]
+    async fn test_stream_nested() {
+        let inner = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let tags = b"\x04\x1aBundle-Format\x0cbinary\x1cBundle-Version\x0a2.0.0\x00";
+        let mut item = vec![];
+        item.extend_from_slice(&3u16.to_le_bytes());
+        item.extend_from_slice(&[0u8; 130]);
+        item.extend_from_slice(&[0u8, 0u8]);
+        item.extend_from_slice(&2u64.to_le_bytes());
+        item.extend_from_slice(&(tags.len() as u64).to_le_bytes());
+        item.extend_from_slice(tags);
+        item.extend_from_slice(&inner);
+        let mut outer = BytesMut::new();
+        outer.extend_from_slice(&1u32.to_le_bytes());
+        outer.extend_from_slice(&[0u8; 28]);
+        outer.extend_from_slice(&(item.len() as u32).to_le_bytes());
+        outer.extend_from_slice(&[0u8; 28]);
+        outer.extend_from_slice(&[1u8; 32]);
+        outer.extend_from_slice(&item);
+
+        let options = ParseOptions {
+            headers_only: true,
+            ..Default::default()
+        };
+        let mut stream = BundleItem::stream(outer.clone())
+            .unwrap()
+            .with_options(options);
+        let parent = stream.next().await.unwrap().unwrap();
+        assert!(!parent.has_data());
+        let nested = parent.stream_nested().unwrap().collect::<Vec<_>>().await;
+        assert_eq!(nested.len(), 71);
+
+        let mut stream = BundleItem::stream(outer).unwrap();
+        let parent = stream.next().await.unwrap().unwrap();
+        assert!(parent.is_bundle());
+        assert_eq!(parent.depth(), 0);
+
+        let mut nested = parent.stream_nested().unwrap();
+        let mut count = 0;
+        while let Some(item) = nested.next().await {
+            let item = item.unwrap();
+            assert!(!item.is_bundle());
+            assert_eq!(item.parent_id, Some(parent.id.clone()));
+            assert_eq!(item.depth(), 1);
+            count += 1;
+        }
+        assert_eq!(count, 71);
+    }
+
+    #[tokio::test]
+    async fn test_stream_tag_validation() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let mut stream = BundleItem::stream(BytesMut::from(data.as_slice())).unwrap();
+        let mut item = stream.next().await.unwrap().unwrap();
+        item.tags.push(Tag::new("Empty", ""));
+        item.encoded_tags = EncodedTags::default();
+        let mut builder = BundleBuilder::new();
+        builder.push_item(&item).unwrap();
+        let bundle = builder.build();
+        let violations = vec![TagViolation::EmptyValue { index: 2 }];
+        assert_eq!(item.validate_tags(), violations);
+
+        for (mode, expected) in [
+            (TagValidation::Disabled, Ok(vec![])),
+            (TagValidation::Lenient, Ok(violations.clone())),
+            (
+                TagValidation::Strict,
+                Err(ParseError::TagViolations(violations.clone())),
+            ),
+        ] {
+            let options = ParseOptions {
+                tag_validation: mode,
+                ..Default::default()
+            };
+            let mut stream = BundleItem::stream(BytesMut::from(bundle.as_ref()))
+                .unwrap()
+                .with_options(options);
+
+            let parsed = stream.next().await.unwrap();
+            assert_eq!(parsed.map(|i| i.tag_violations), expected);
+        }
+    }
 }
@@ -0,0 +1,136 @@
+use super::tags::Tag;
+
+/// Content type of data that cannot be identified.
+pub const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+/// Bytes at the start of the data inspected to guess its content type.
+const SNIFF_SIZE: usize = 512;
+
+/// Signatures at the start of the data of common binary formats.
+const MAGIC_BYTES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"ID3", "audio/mpeg"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"\x1a\x45\xdf\xa3", "video/webm"),
+    (b"\x00asm", "application/wasm"),
+];
+
+/// Content type given by the `Content-Type` tag, or sniffed from the data
+/// when the tag is absent.
+pub fn content_type(tags: &[Tag], data: &[u8]) -> String {
+    tags.iter()
+        .find(|t| t.name().eq_ignore_ascii_case("Content-Type"))
+        .map(|t| t.value().to_string())
+        .unwrap_or_else(|| sniff(data).to_string())
+}
+
+/// Guess the content type from the first bytes of the data, at most
+/// [`SNIFF_SIZE`] of them.
+pub fn sniff(data: &[u8]) -> &'static str {
+    if let Some((_, content_type)) = MAGIC_BYTES.iter().find(|(m, _)| data.starts_with(m)) {
+        return content_type;
+    }
+    match data {
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => return "image/webp",
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => return "video/mp4",
+        [] => return DEFAULT_CONTENT_TYPE,
+        _ => {}
+    }
+    let prefix = &data[..data.len().min(SNIFF_SIZE)];
+    let truncated = prefix.len() < data.len();
+    let text = match std::str::from_utf8(prefix) {
+        Ok(text) => text,
+        // Character cut at the end of the prefix
+        Err(e) if truncated && e.error_len().is_none() => {
+            std::str::from_utf8(&prefix[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return DEFAULT_CONTENT_TYPE,
+    };
+    if text
+        .chars()
+        .any(|c| c.is_control() && !c.is_ascii_whitespace())
+    {
+        return DEFAULT_CONTENT_TYPE;
+    }
+    let text = text.trim_start();
+    let starts_with = |prefix: &str| {
+        text.get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+    };
+    if starts_with("<!doctype html") || starts_with("<html") {
+        "text/html"
+    } else if starts_with("<svg") || (starts_with("<?xml") && text.contains("<svg")) {
+        "image/svg+xml"
+    } else if starts_with("<?xml") {
+        "application/xml"
+    } else if (text.starts_with('{') || text.starts_with('[')) && is_json(text, truncated) {
+        "application/json"
+    } else {
+        "text/plain"
+    }
+}
+
+/// Whether the text is JSON, or the start of JSON when it is `truncated`.
+fn is_json(text: &str, truncated: bool) -> bool {
+    match serde_json::from_str::<serde::de::IgnoredAny>(text) {
+        Ok(_) => true,
+        Err(e) => truncated && e.is_eof(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_type_from_tag() {
+        let tags = vec![Tag::new("content-type", "image/png")];
+        assert_eq!(content_type(&tags, b"{}"), "image/png");
+        assert_eq!(content_type(&[], b"{}"), "application/json");
+    }
+
+    #[test]
+    fn test_sniff() {
+        for (data, expected) in [
+            (&b"\x89PNG\r\n\x1a\n\x00\x00"[..], "image/png"),
+            (b"\xff\xd8\xff\xe0", "image/jpeg"),
+            (b"RIFF\x00\x00\x00\x00WEBPVP8 ", "image/webp"),
+            (b"\x00\x00\x00\x18ftypmp42", "video/mp4"),
+            (b"%PDF-1.7", "application/pdf"),
+            (b"  <!DOCTYPE html><html></html>", "text/html"),
+            (b"<?xml version=\"1.0\"?><svg></svg>", "image/svg+xml"),
+            (b"<?xml version=\"1.0\"?><feed></feed>", "application/xml"),
+            (b"{\"manifest\":\"arweave/paths\"}", "application/json"),
+            (b"{not json", "text/plain"),
+            (b"hello world", "text/plain"),
+            (b"\x00\x01\x02\xff", DEFAULT_CONTENT_TYPE),
+            (b"", DEFAULT_CONTENT_TYPE),
+        ] {
+            assert_eq!(sniff(data), expected);
+        }
+    }
+
+    #[test]
+    fn test_sniff_prefix() {
+        let json = format!("[{}1]", "1,".repeat(SNIFF_SIZE));
+        assert_eq!(sniff(json.as_bytes()), "application/json");
+        let json = format!("{{\"a\":1}}{}", " x".repeat(SNIFF_SIZE));
+        assert_eq!(sniff(json.as_bytes()), "text/plain");
+
+        // Multi-byte character cut at the end of the prefix
+        let text = "é".repeat(SNIFF_SIZE);
+        assert_eq!(sniff(text.as_bytes()), "text/plain");
+
+        let binary = [b"hello".as_slice(), &[0u8; SNIFF_SIZE]].concat();
+        assert_eq!(sniff(&binary), DEFAULT_CONTENT_TYPE);
+        assert_eq!(sniff(b"line\r\n\tindented\x0c"), "text/plain");
+        assert_eq!(sniff(b"bell\x07"), DEFAULT_CONTENT_TYPE);
+        assert_eq!(sniff(b"{\"a\":\"\x1b\"}"), DEFAULT_CONTENT_TYPE);
+    }
+}
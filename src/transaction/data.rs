@@ -0,0 +1,227 @@
+use base64::{decode_config, encode_config, URL_SAFE_NO_PAD};
+use bytes::Bytes;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::Deref;
+use std::str::FromStr;
+
+/// How the data of an item is written when the item is serialized.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DataEncoding {
+    /// Base64url string without padding.
+    #[default]
+    Base64,
+    /// Lowercase hexadecimal string.
+    Hex,
+    /// The data field is left out.
+    Omit,
+}
+
+impl FromStr for DataEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base64" => Ok(DataEncoding::Base64),
+            "hex" => Ok(DataEncoding::Hex),
+            "omit" => Ok(DataEncoding::Omit),
+            _ => Err(format!("Invalid data encoding {}", s)),
+        }
+    }
+}
+
+/// Raw data of an item, sliced from the bundle without copying.
+///
+/// It is serialized with its [`DataEncoding`]: base64url data as a plain
+/// string, hex data as `{"encoding": "hex", "value": ...}` so that it can be
+/// told apart when deserialized. Two instances are equal when their bytes are
+/// equal, whatever their encoding.
+#[derive(Debug, Default, Clone)]
+pub struct ItemData {
+    bytes: Bytes,
+    encoding: DataEncoding,
+    /// Data of items parsed headers only, never serialized.
+    skipped: bool,
+}
+
+impl ItemData {
+    pub fn bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+
+    pub fn encoding(&self) -> DataEncoding {
+        self.encoding
+    }
+
+    /// Has no effect on skipped data.
+    pub fn set_encoding(&mut self, encoding: DataEncoding) {
+        if !self.skipped {
+            self.encoding = encoding;
+        }
+    }
+
+    /// Whether the data was left out while parsing the item headers only.
+    /// The bytes are still available for nested bundles.
+    pub fn is_skipped(&self) -> bool {
+        self.skipped
+    }
+
+    pub(crate) fn skipped(bytes: Bytes) -> Self {
+        Self {
+            bytes,
+            encoding: DataEncoding::Omit,
+            skipped: true,
+        }
+    }
+
+    /// Data left out of a serialized item, read back as skipped.
+    pub(crate) fn omitted() -> Self {
+        Self::skipped(Bytes::new())
+    }
+
+    pub(crate) fn is_omitted(&self) -> bool {
+        self.encoding == DataEncoding::Omit
+    }
+}
+
+impl From<Bytes> for ItemData {
+    fn from(bytes: Bytes) -> Self {
+        Self {
+            bytes,
+            encoding: DataEncoding::default(),
+            skipped: false,
+        }
+    }
+}
+
+impl Deref for ItemData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl PartialEq for ItemData {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for ItemData {}
+
+impl Serialize for ItemData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.encoding {
+            DataEncoding::Base64 => {
+                serializer.serialize_str(&encode_config(&self.bytes, URL_SAFE_NO_PAD))
+            }
+            DataEncoding::Hex => {
+                let mut data = serializer.serialize_struct("ItemData", 2)?;
+                data.serialize_field("encoding", "hex")?;
+                data.serialize_field("value", &hex::encode(&self.bytes))?;
+                data.end()
+            }
+            DataEncoding::Omit => serializer.serialize_none(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Encoding {
+    Base64,
+    Hex,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EncodedData {
+    Base64(String),
+    Tagged { encoding: Encoding, value: String },
+}
+
+impl<'de> Deserialize<'de> for ItemData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (encoding, bytes) = match Option::<EncodedData>::deserialize(deserializer)? {
+            None => return Ok(Self::omitted()),
+            Some(EncodedData::Base64(value))
+            | Some(EncodedData::Tagged {
+                encoding: Encoding::Base64,
+                value,
+            }) => (
+                DataEncoding::Base64,
+                decode_config(value, URL_SAFE_NO_PAD).map_err(serde::de::Error::custom)?,
+            ),
+            Some(EncodedData::Tagged {
+                encoding: Encoding::Hex,
+                value,
+            }) => (
+                DataEncoding::Hex,
+                hex::decode(value).map_err(serde::de::Error::custom)?,
+            ),
+        };
+        Ok(Self {
+            bytes: Bytes::from(bytes),
+            encoding,
+            skipped: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_encodings() {
+        let mut data = ItemData::from(Bytes::from_static(b"\x00\xfehello"));
+        for (encoding, expected) in [
+            (DataEncoding::Base64, "\"AP5oZWxsbw\""),
+            (
+                DataEncoding::Hex,
+                "{\"encoding\":\"hex\",\"value\":\"00fe68656c6c6f\"}",
+            ),
+            (DataEncoding::Omit, "null"),
+        ] {
+            data.set_encoding(encoding);
+            assert_eq!(serde_json::to_string(&data).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_skipped() {
+        let mut data = ItemData::skipped(Bytes::from_static(b"hello"));
+        data.set_encoding(DataEncoding::Base64);
+        assert!(data.is_omitted());
+    }
+
+    #[test]
+    fn test_deserialize_base64() {
+        let data: ItemData = serde_json::from_str("\"AP5oZWxsbw\"").unwrap();
+        assert_eq!(data.bytes().as_ref(), b"\x00\xfehello");
+
+        let result = serde_json::from_str::<ItemData>("\"AP5*\"");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_encodings() {
+        for encoding in [DataEncoding::Base64, DataEncoding::Hex] {
+            let mut data = ItemData::from(Bytes::from_static(b"\x00\xfehello"));
+            data.set_encoding(encoding);
+            let json = serde_json::to_string(&data).unwrap();
+            let deserialized: ItemData = serde_json::from_str(&json).unwrap();
+            assert_eq!(deserialized.bytes(), data.bytes());
+            assert_eq!(deserialized.encoding(), encoding);
+        }
+
+        let data: ItemData = serde_json::from_str("null").unwrap();
+        assert!(data.is_skipped());
+
+        let result = serde_json::from_str::<ItemData>("{\"encoding\":\"hex\",\"value\":\"0g\"}");
+        assert!(result.is_err());
+        let result = serde_json::from_str::<ItemData>("{\"encoding\":\"b58\",\"value\":\"\"}");
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use sha2::{Digest, Sha384};
+
+/// Size in bytes of a deep hash (SHA-384).
+pub const DEEP_HASH_SIZE: usize = 48;
+
+/// Nested structure hashed by Arweave's `deepHash` algorithm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeepHashChunk<'a> {
+    Blob(&'a [u8]),
+    List(Vec<DeepHashChunk<'a>>),
+}
+
+impl<'a> From<&'a [u8]> for DeepHashChunk<'a> {
+    fn from(value: &'a [u8]) -> Self {
+        DeepHashChunk::Blob(value)
+    }
+}
+
+impl<'a> From<Vec<DeepHashChunk<'a>>> for DeepHashChunk<'a> {
+    fn from(value: Vec<DeepHashChunk<'a>>) -> Self {
+        DeepHashChunk::List(value)
+    }
+}
+
+/// Computes Arweave's `deepHash` of a chunk.
+///
+/// A blob is hashed as `SHA-384(SHA-384("blob" || len) || SHA-384(data))`, while a
+/// list starts from `SHA-384("list" || len)` and folds each element as
+/// `SHA-384(acc || deep_hash(element))`.
+pub fn deep_hash(chunk: &DeepHashChunk) -> [u8; DEEP_HASH_SIZE] {
+    match chunk {
+        DeepHashChunk::Blob(data) => {
+            let tag = Sha384::digest(format!("blob{}", data.len()));
+            Sha384::new()
+                .chain_update(tag)
+                .chain_update(Sha384::digest(data))
+                .finalize()
+                .into()
+        }
+        DeepHashChunk::List(chunks) => {
+            let tag = Sha384::digest(format!("list{}", chunks.len()));
+            chunks.iter().fold(tag.into(), |acc, chunk| {
+                Sha384::new()
+                    .chain_update(acc)
+                    .chain_update(deep_hash(chunk))
+                    .finalize()
+                    .into()
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deep_hash_blob() {
+        let vectors: [(&[u8], &str); 2] = [
+            (b"", "fbf00cc444f5fea9dc3bedf62a13fba8ae87e7445fc910567a23bec4eb82fadb1143c433069314d8362983dc3c2e4a38"),
+            (b"hello", "33ab2407a6c328c0bc1bbe5971f49af5c1908985f83c3d2bd89a9e221dd8b068dc61ce968ba3f9ab12d5361ba3944382"),
+        ];
+        for (data, expected) in vectors {
+            assert_eq!(hex::encode(deep_hash(&data.into())), expected);
+        }
+
+        let data = (0..1000).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+        assert_eq!(
+            hex::encode(deep_hash(&data.as_slice().into())),
+            "2886d9746a47b8d96fb806157b612b785a8f81f7b008d788cfe1005dbdc9ce94b2a6027e6d557d9ada5e69753eac4f9d"
+        );
+    }
+
+    #[test]
+    fn test_deep_hash_list() {
+        assert_eq!(
+            hex::encode(deep_hash(&DeepHashChunk::List(vec![]))),
+            "a69e7d37fdc7f040a9ec16aae84de24fab4a653dac4de0bd247e36bab9fe45d9289c5a04a893c95285812f5cefc9707a"
+        );
+
+        let nested = DeepHashChunk::List(vec![
+            DeepHashChunk::Blob(b"a"),
+            DeepHashChunk::List(vec![DeepHashChunk::Blob(b"b"), DeepHashChunk::Blob(b"c")]),
+            DeepHashChunk::List(vec![]),
+        ]);
+        assert_eq!(
+            hex::encode(deep_hash(&nested)),
+            "e9714131620fcd0c00fcb9121bec9d8f04218190f4430781e64e7310b83fe050e60a9b52d07e4d72e87c88ea5fa5c98d"
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use super::address::owner_address;
+use super::bundle::{
+    read_header_int, BundleItem, ItemReader, ParseOptions, SignatureType, BUNDLE_HEADER_SIZE,
+    ENTRY_HEADER_SIZE, ID_SIZE, SIG_CONFIG,
+};
+use super::tags::{Tag, TagsReader};
+use super::verify::SignedFields;
+use crate::errors::ParseError;
+use base64::{encode_config, URL_SAFE_NO_PAD};
+use bytes::Bytes;
+
+type Result<T> = std::result::Result<T, ParseError>;
+
+/// Borrowed view of a data item over the buffer holding its bundle.
+///
+/// Fields are slices of the buffer and are only decoded when asked for, which
+/// makes filtering or counting items much cheaper than building a
+/// [`BundleItem`] for each of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BundleItemRef<'a> {
+    id: &'a [u8],
+    offset: usize,
+    size: usize,
+    signature_type: SignatureType,
+    signature: &'a [u8],
+    owner: &'a [u8],
+    target: Option<&'a [u8]>,
+    anchor: Option<&'a [u8]>,
+    tags_count: u64,
+    tags: &'a [u8],
+    data: &'a [u8],
+}
+
+impl<'a> BundleItemRef<'a> {
+    /// Parse the item in `data` with the raw `id` of its header entry, `offset`
+    /// being the position of the item within its bundle.
+    pub fn parse(data: &'a [u8], id: &'a [u8], offset: usize) -> Result<Self> {
+        let size = data.len();
+        let mut data = ItemReader { data, offset };
+
+        let signature_type: SignatureType = data.read_u16_le("signature type")?.try_into()?;
+        let (sig_size, owner_size) = SIG_CONFIG[&signature_type];
+        let signature = data.read_slice(sig_size, "signature")?;
+        let owner = data.read_slice(owner_size, "owner")?;
+
+        let target = Self::read_optional_slice(&mut data, "target")?;
+        let anchor = Self::read_optional_slice(&mut data, "anchor")?;
+
+        let tags_count = data.read_u64_le("tags count")?;
+        let tags_size = data.read_u64_le("tags size")?;
+        let tags = data.read_slice(tags_size as usize, "tags")?;
+
+        Ok(Self {
+            id,
+            offset,
+            size,
+            signature_type,
+            signature,
+            owner,
+            target,
+            anchor,
+            tags_count,
+            tags,
+            data: data.data,
+        })
+    }
+
+    /// Base64url ID of the item, as found in the header table.
+    pub fn id(&self) -> String {
+        encode_config(self.id, URL_SAFE_NO_PAD)
+    }
+
+    pub fn raw_id(&self) -> &'a [u8] {
+        self.id
+    }
+
+    /// Position of the item within its bundle.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Size of the whole item in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn signature_type(&self) -> SignatureType {
+        self.signature_type
+    }
+
+    pub fn signature(&self) -> &'a [u8] {
+        self.signature
+    }
+
+    pub fn owner(&self) -> &'a [u8] {
+        self.owner
+    }
+
+    /// Wallet address of the owner, see [`owner_address`].
+    pub fn owner_address(&self) -> Option<String> {
+        owner_address(self.signature_type, self.owner)
+    }
+
+    pub fn target(&self) -> Option<&'a [u8]> {
+        self.target
+    }
+
+    pub fn anchor(&self) -> Option<&'a [u8]> {
+        self.anchor
+    }
+
+    /// Avro encoded tags.
+    pub fn raw_tags(&self) -> &'a [u8] {
+        self.tags
+    }
+
+    pub fn tags(&self) -> Result<Vec<Tag>> {
+        if self.tags_count == 0 || self.tags.is_empty() {
+            return Ok(vec![]);
+        }
+        let tags = TagsReader::deserialize(self.tags)?;
+        if tags.len() as u64 != self.tags_count {
+            return Err(ParseError::InvalidTagsLength(self.tags_count, tags.len()));
+        }
+        Ok(tags)
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Position of the data within the bundle.
+    pub fn data_offset(&self) -> usize {
+        self.offset + self.size - self.data.len()
+    }
+
+    /// Build the owned item, copying the data out of the buffer.
+    pub fn to_item(&self, options: &ParseOptions) -> Result<BundleItem> {
+        BundleItem::from_ref(self, options, Bytes::copy_from_slice)
+    }
+
+    pub(crate) fn signed_fields(&self) -> SignedFields<'a> {
+        SignedFields {
+            signature_type: self.signature_type,
+            signature: self.signature,
+            owner: self.owner,
+            target: self.target,
+            anchor: self.anchor,
+            tags: self.tags,
+            data: self.data,
+        }
+    }
+
+    fn read_optional_slice(
+        data: &mut ItemReader<&'a [u8]>,
+        field: &'static str,
+    ) -> Result<Option<&'a [u8]>> {
+        match data.read_u8(field)? {
+            0 => Ok(None),
+            1 => Ok(Some(data.read_slice(ID_SIZE, field)?)),
+            b => Err(ParseError::InvalidPresenceByte(b)),
+        }
+    }
+}
+
+/// Iterator over the items of a bundle fully loaded in memory, without copying
+/// nor decoding them.
+pub struct BundleItemRefs<'a> {
+    bundle: &'a [u8],
+    num_entries: usize,
+    current_entry: usize,
+    offset: usize,
+}
+
+impl<'a> BundleItemRefs<'a> {
+    pub fn new(bundle: &'a [u8]) -> Result<Self> {
+        let mut header = ItemReader {
+            data: bundle,
+            offset: 0,
+        };
+        let num_entries = read_header_int(
+            header.read_slice(BUNDLE_HEADER_SIZE, "entries count")?,
+            "entries count",
+            0,
+        )? as usize;
+        let offset = BUNDLE_HEADER_SIZE + num_entries * ENTRY_HEADER_SIZE;
+        if bundle.len() < offset {
+            return Err(ParseError::UnexpectedEof {
+                field: "entry header",
+                offset: bundle.len() - (bundle.len() - BUNDLE_HEADER_SIZE) % ENTRY_HEADER_SIZE,
+            });
+        }
+        Ok(Self {
+            bundle,
+            num_entries,
+            current_entry: 0,
+            offset,
+        })
+    }
+
+    /// Number of items in the bundle.
+    pub fn num_entries(&self) -> usize {
+        self.num_entries
+    }
+}
+
+impl<'a> Iterator for BundleItemRefs<'a> {
+    type Item = Result<BundleItemRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current_entry >= self.num_entries {
+            return None;
+        }
+        let entry = BUNDLE_HEADER_SIZE + self.current_entry * ENTRY_HEADER_SIZE;
+        let size = match read_header_int(&self.bundle[entry..], "entry size", entry) {
+            Ok(size) => size as usize,
+            Err(e) => {
+                self.current_entry = self.num_entries;
+                return Some(Err(e));
+            }
+        };
+        let id = &self.bundle[entry + ENTRY_HEADER_SIZE - ID_SIZE..entry + ENTRY_HEADER_SIZE];
+        self.current_entry += 1;
+        let Some(data) = self.bundle.get(self.offset..self.offset + size) else {
+            // Nothing can be read past a truncated item
+            self.current_entry = self.num_entries;
+            return Some(Err(ParseError::UnexpectedEof {
+                field: "item",
+                offset: self.bundle.len(),
+            }));
+        };
+        let item = BundleItemRef::parse(data, id, self.offset);
+        self.offset += size;
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::builder::BundleBuilder;
+    use bytes::BytesMut;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn test_item_refs() {
+        let data = tokio::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc")
+            .await
+            .unwrap();
+        let mut expected = BundleItem::stream(BytesMut::from(data.as_slice())).unwrap();
+
+        let items = BundleItemRefs::new(&data).unwrap();
+        assert_eq!(items.num_entries(), 71);
+        let mut count = 0;
+        for item in items {
+            let item = item.unwrap();
+            let expected = expected.next().await.unwrap().unwrap();
+            assert_eq!(item.id(), expected.id());
+            assert_eq!(item.data(), expected.data());
+            assert_eq!(item.tags().unwrap().len() as u64, item.tags_count);
+            assert_eq!(item.to_item(&ParseOptions::default()).unwrap(), expected);
+            count += 1;
+        }
+        assert_eq!(count, 71);
+    }
+
+    #[tokio::test]
+    async fn test_item_ref_empty_tags_with_size() {
+        // No tags but an empty Avro block padded to 4 bytes
+        let mut item = vec![];
+        item.extend_from_slice(&2u16.to_le_bytes());
+        item.extend_from_slice(&[1u8; 64]);
+        item.extend_from_slice(&[0u8; 32]);
+        item.extend_from_slice(&[0, 0]);
+        item.extend_from_slice(&0u64.to_le_bytes());
+        item.extend_from_slice(&4u64.to_le_bytes());
+        item.extend_from_slice(&[0u8; 4]);
+        item.extend_from_slice(b"data");
+        let mut builder = BundleBuilder::new();
+        builder.push_raw_item(item.clone()).unwrap();
+        let data = builder.build();
+
+        let item_ref = BundleItemRefs::new(&data).unwrap().next().unwrap().unwrap();
+        let mut stream = BundleItem::stream(BytesMut::from(&data[..])).unwrap();
+        let expected = stream.next().await.unwrap().unwrap();
+
+        assert_eq!(item_ref.raw_tags(), &[0u8; 4]);
+        assert_eq!(item_ref.tags().unwrap(), vec![]);
+        assert_eq!(item_ref.data(), b"data");
+        assert_eq!(item_ref.data(), expected.data());
+        assert_eq!(
+            item_ref.to_item(&ParseOptions::default()).unwrap(),
+            expected
+        );
+        assert_eq!(expected.to_bytes().unwrap(), item);
+    }
+
+    #[test]
+    fn test_item_refs_truncated() {
+        let data =
+            std::fs::read("tests/fixtures/NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc").unwrap();
+
+        let result = BundleItemRefs::new(&data[..100]);
+        assert_eq!(
+            result.err(),
+            Some(ParseError::UnexpectedEof {
+                field: "entry header",
+                offset: 96
+            })
+        );
+
+        let results = BundleItemRefs::new(&data[..data.len() - 10])
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 71);
+        assert!(results[..70].iter().all(|r| r.is_ok()));
+        assert_eq!(
+            results[70],
+            Err(ParseError::UnexpectedEof {
+                field: "item",
+                offset: data.len() - 10
+            })
+        );
+    }
+}
@@ -1,2 +1,10 @@
+pub mod address;
+pub mod builder;
 pub mod bundle;
+pub mod content_type;
+pub mod data;
+pub mod deep_hash;
+pub mod item_ref;
 pub mod tags;
+pub mod validation;
+pub mod verify;
@@ -4,10 +4,32 @@ use std::vec::Vec;
 
 type Result<T> = std::result::Result<T, ParseError>;
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct Tag {
-    name: String,
-    value: String,
+    pub(crate) name: String,
+    pub(crate) value: String,
+}
+
+impl Tag {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Encode tags in the Avro format used by data items, see [`TagsWriter`].
+    pub fn encode_all(tags: &[Tag]) -> Vec<u8> {
+        TagsWriter::serialize(tags)
+    }
 }
 
 pub struct TagsReader<'a> {
@@ -20,58 +42,66 @@ impl<'a> TagsReader<'a> {
         TagsReader { buffer, pos: 0 }.read_tags()
     }
 
-    fn skip_long(&mut self) {
-        while self.buffer.get(self.pos).map_or(false, |&b| b & 0x80 != 0) {
-            self.pos += 1;
-        }
-        // Move past the last byte that was part of the long
-        if self.pos < self.buffer.len() {
-            self.pos += 1;
-        }
-    }
-
     fn read_tags(&mut self) -> Result<Vec<Tag>> {
         let mut tags = Vec::new();
 
-        while let Some(ref mut length) = self.read_long() {
-            if length.is_negative() {
-                *length = length.abs();
-                self.skip_long()
+        // Arrays are a sequence of blocks terminated by a block of count 0. A
+        // negative count is followed by the size in bytes of the block.
+        loop {
+            let count = self.read_long()?;
+            if count == 0 {
+                break;
             }
+            let block_size = if count < 0 {
+                Some(self.read_long()?)
+            } else {
+                None
+            };
 
-            for _ in 0..*length {
+            let start_pos = self.pos;
+            for _ in 0..count.unsigned_abs() {
                 let name = self.read_string()?;
                 let value = self.read_string()?;
                 tags.push(Tag { name, value });
             }
+
+            if let Some(expected) = block_size {
+                let actual = self.pos - start_pos;
+                if expected < 0 || expected as usize != actual {
+                    return Err(ParseError::InvalidBlockSize(expected, actual));
+                }
+            }
         }
 
         Ok(tags)
     }
 
-    fn read_long(&mut self) -> Option<i64> {
-        let mut number = 0i64;
-        let mut shift = 0usize;
+    /// Reads a zig-zag encoded variable-length long of at most 10 bytes.
+    fn read_long(&mut self) -> Result<i64> {
+        let mut number = 0u64;
 
-        while self.pos < self.buffer.len() {
-            let byte = self.buffer[self.pos];
+        for shift in (0..64).step_by(7) {
+            let byte = *self.buffer.get(self.pos).ok_or(ParseError::ExpectedLong)?;
             self.pos += 1;
 
-            number |= ((byte & 0x7f) as i64) << shift;
-            shift += 7;
+            // Only the lowest bit of the 10th byte fits in 64 bits
+            if shift == 63 && byte > 1 {
+                return Err(ParseError::LongOverflow);
+            }
+            number |= ((byte & 0x7f) as u64) << shift;
 
-            if byte & 0x80 == 0 || shift >= 28 {
-                return Some((number >> 1) ^ -(number & 1));
+            if byte & 0x80 == 0 {
+                return Ok((number >> 1) as i64 ^ -((number & 1) as i64));
             }
         }
 
-        None
+        Err(ParseError::LongOverflow)
     }
 
     fn read_string(&mut self) -> Result<String> {
-        let length = self.read_long().ok_or(ParseError::ExpectedLong)?;
+        let length = self.read_long()?;
 
-        if length < 0 || (self.pos + length as usize) > self.buffer.len() {
+        if length < 0 || length as u64 > (self.buffer.len() - self.pos) as u64 {
             return Err(ParseError::InvalidLengthString);
         }
 
@@ -83,6 +113,47 @@ impl<'a> TagsReader<'a> {
     }
 }
 
+/// Encodes tags as an Avro array of records with `name` and `value` strings,
+/// written as a single block with a negative count followed by its size in
+/// bytes, which is how tags are encoded by ANS-104 clients.
+pub struct TagsWriter {
+    buffer: Vec<u8>,
+}
+
+impl TagsWriter {
+    pub fn serialize(tags: &[Tag]) -> Vec<u8> {
+        if tags.is_empty() {
+            return vec![];
+        }
+        let mut block = TagsWriter { buffer: vec![] };
+        for tag in tags {
+            block.write_string(&tag.name);
+            block.write_string(&tag.value);
+        }
+
+        let mut writer = TagsWriter { buffer: vec![] };
+        writer.write_long(-(tags.len() as i64));
+        writer.write_long(block.buffer.len() as i64);
+        writer.buffer.extend_from_slice(&block.buffer);
+        writer.write_long(0);
+        writer.buffer
+    }
+
+    fn write_long(&mut self, value: i64) {
+        let mut number = ((value << 1) ^ (value >> 63)) as u64;
+        while number & !0x7f != 0 {
+            self.buffer.push((number & 0x7f) as u8 | 0x80);
+            number >>= 7;
+        }
+        self.buffer.push(number as u8);
+    }
+
+    fn write_string(&mut self, value: &str) {
+        self.write_long(value.len() as i64);
+        self.buffer.extend_from_slice(value.as_bytes());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -877,6 +948,121 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_serialize_tags() {
+        let fixtures = std::fs::read_dir("tests/fixtures").unwrap();
+        for f in fixtures {
+            let file = f.unwrap();
+            let file_name = file.file_name().to_str().unwrap().to_string();
+            if file_name.contains("NbhWDsl290UWZ7GfMIM2xdrgCORceNgzmHf4l2uDypc") {
+                continue;
+            }
+            let buffer = std::fs::read(file.path()).unwrap();
+            let tags = &EXPECTED_TAGS[file_name.as_str()];
+            assert_eq!(Tag::encode_all(tags), buffer);
+        }
+    }
+
+    #[test]
+    fn test_serialize_tags_round_trip() {
+        let tags = vec![
+            Tag {
+                name: "".to_owned(),
+                value: "x".repeat(300),
+            },
+            Tag {
+                name: "Ñandú".to_owned(),
+                value: "y".repeat(20_000),
+            },
+        ];
+
+        let buffer = TagsWriter::serialize(&tags);
+
+        // Block count -2 and block size 20_314 as zig-zag varints
+        assert_eq!(&buffer[..4], &[0x03, 0xb4, 0xbd, 0x02]);
+        assert_eq!(buffer.last(), Some(&0));
+        assert_eq!(TagsReader::deserialize(&buffer).unwrap(), tags);
+    }
+
+    #[test]
+    fn test_serialize_no_tags() {
+        assert!(Tag::encode_all(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_read_long() {
+        let values = [
+            0,
+            -1,
+            63,
+            -64,
+            1 << 28,
+            -(1 << 35) - 7,
+            i32::MAX as i64 + 1,
+            i64::MAX,
+            i64::MIN,
+        ];
+        for value in values {
+            let mut writer = TagsWriter { buffer: vec![] };
+            writer.write_long(value);
+            let mut reader = TagsReader {
+                buffer: &writer.buffer,
+                pos: 0,
+            };
+            assert_eq!(reader.read_long(), Ok(value));
+            assert_eq!(reader.pos, writer.buffer.len());
+        }
+    }
+
+    #[test]
+    fn test_read_long_malformed() {
+        let overflow = [0xff; 11];
+        let overflow_last_byte = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02];
+        let unterminated = [0x80, 0x80];
+        for (buffer, expected) in [
+            (&overflow[..], ParseError::LongOverflow),
+            (&overflow_last_byte[..], ParseError::LongOverflow),
+            (&unterminated[..], ParseError::ExpectedLong),
+        ] {
+            let mut reader = TagsReader { buffer, pos: 0 };
+            assert_eq!(reader.read_long(), Err(expected));
+        }
+    }
+
+    #[test]
+    fn test_deserialize_tags_blocks() {
+        // A block with a positive count followed by a block with a negative count
+        let buffer = b"\x02\x02a\x02b\x01\x0a\x02c\x04de\x00";
+        let tags = TagsReader::deserialize(buffer).unwrap();
+        assert_eq!(
+            tags,
+            vec![
+                Tag {
+                    name: "a".to_owned(),
+                    value: "b".to_owned()
+                },
+                Tag {
+                    name: "c".to_owned(),
+                    value: "de".to_owned()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deserialize_tags_invalid_block_size() {
+        let buffer = b"\x01\x08\x02c\x04de\x00";
+        let result = TagsReader::deserialize(buffer);
+        assert_eq!(result, Err(ParseError::InvalidBlockSize(4, 5)));
+    }
+
+    #[test]
+    fn test_deserialize_tags_missing_terminator() {
+        let buffer = b"\x02\x02a\x02b";
+        let result = TagsReader::deserialize(buffer);
+        assert_eq!(result, Err(ParseError::ExpectedLong));
+    }
+
     #[test]
     fn test_deserialize_tags_invalid_length() {
         let buffer = b"\x02\x00\x03\x00\x04\x00\x05\x00\x06\x00\x07\x00";
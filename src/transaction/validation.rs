@@ -0,0 +1,134 @@
+use super::tags::Tag;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Maximum number of tags of a data item.
+pub const MAX_TAGS: usize = 128;
+/// Maximum size in bytes of a tag name.
+pub const MAX_TAG_NAME_SIZE: usize = 1024;
+/// Maximum size in bytes of a tag value.
+pub const MAX_TAG_VALUE_SIZE: usize = 3072;
+
+/// Tag of a data item that does not follow the ANS-104 limits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Error)]
+#[serde(tag = "violation", rename_all = "snake_case")]
+pub enum TagViolation {
+    #[error("Too many tags {count}. Maximum is {MAX_TAGS}")]
+    TooManyTags { count: usize },
+    #[error("Tag {index} name has {size} bytes. Maximum is {MAX_TAG_NAME_SIZE}")]
+    NameTooLong { index: usize, size: usize },
+    #[error("Tag {index} value has {size} bytes. Maximum is {MAX_TAG_VALUE_SIZE}")]
+    ValueTooLong { index: usize, size: usize },
+    #[error("Tag {index} has an empty name")]
+    EmptyName { index: usize },
+    #[error("Tag {index} has an empty value")]
+    EmptyValue { index: usize },
+}
+
+/// How tag violations are handled while parsing a bundle.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TagValidation {
+    #[default]
+    Disabled,
+    /// Violations are recorded on the item.
+    Lenient,
+    /// Items with any violation are rejected.
+    Strict,
+}
+
+impl FromStr for TagValidation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disabled" => Ok(TagValidation::Disabled),
+            "lenient" => Ok(TagValidation::Lenient),
+            "strict" => Ok(TagValidation::Strict),
+            _ => Err(format!("Invalid tag validation mode {}", s)),
+        }
+    }
+}
+
+/// Check tags against the limits of the ANS-104 specification.
+pub fn validate_tags(tags: &[Tag]) -> Vec<TagViolation> {
+    let mut violations = vec![];
+    if tags.len() > MAX_TAGS {
+        violations.push(TagViolation::TooManyTags { count: tags.len() });
+    }
+    for (index, tag) in tags.iter().enumerate() {
+        if tag.name.is_empty() {
+            violations.push(TagViolation::EmptyName { index });
+        } else if tag.name.len() > MAX_TAG_NAME_SIZE {
+            violations.push(TagViolation::NameTooLong {
+                index,
+                size: tag.name.len(),
+            });
+        }
+        if tag.value.is_empty() {
+            violations.push(TagViolation::EmptyValue { index });
+        } else if tag.value.len() > MAX_TAG_VALUE_SIZE {
+            violations.push(TagViolation::ValueTooLong {
+                index,
+                size: tag.value.len(),
+            });
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(name: &str, value: &str) -> Tag {
+        Tag::new(name, value)
+    }
+
+    #[test]
+    fn test_validate_tags() {
+        let tags = vec![
+            tag("Content-Type", "application/json"),
+            tag("", "value"),
+            tag("name", ""),
+            tag(
+                &"n".repeat(MAX_TAG_NAME_SIZE + 1),
+                &"v".repeat(MAX_TAG_VALUE_SIZE + 1),
+            ),
+            tag(
+                &"n".repeat(MAX_TAG_NAME_SIZE),
+                &"v".repeat(MAX_TAG_VALUE_SIZE),
+            ),
+        ];
+
+        assert_eq!(
+            validate_tags(&tags),
+            vec![
+                TagViolation::EmptyName { index: 1 },
+                TagViolation::EmptyValue { index: 2 },
+                TagViolation::NameTooLong {
+                    index: 3,
+                    size: MAX_TAG_NAME_SIZE + 1
+                },
+                TagViolation::ValueTooLong {
+                    index: 3,
+                    size: MAX_TAG_VALUE_SIZE + 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_too_many_tags() {
+        let tags = (0..MAX_TAGS).map(|_| tag("a", "b")).collect::<Vec<_>>();
+        assert!(validate_tags(&tags).is_empty());
+
+        let tags = (0..=MAX_TAGS).map(|_| tag("a", "b")).collect::<Vec<_>>();
+        assert_eq!(
+            validate_tags(&tags),
+            vec![TagViolation::TooManyTags {
+                count: MAX_TAGS + 1
+            }]
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use super::bundle::SignatureType;
+use super::deep_hash::{deep_hash, DeepHashChunk, DEEP_HASH_SIZE};
+use ed25519_dalek::Verifier;
+use k256::ecdsa::signature::DigestVerifier;
+use rsa::{BigUint, Pss, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use std::str::FromStr;
+
+/// Result of verifying the signature of a data item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureStatus {
+    Valid,
+    Invalid,
+    /// The signature scheme of the item cannot be verified yet. Only Arweave,
+    /// ED25519, Solana and Ethereum signatures are verified.
+    Unsupported,
+}
+
+/// What to do with the signature of each item while parsing a bundle.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureVerification {
+    #[default]
+    Disabled,
+    /// Verify and record the [`SignatureStatus`] on each item.
+    Record,
+    /// Same as `Record` but items with an invalid signature are rejected.
+    Reject,
+}
+
+impl FromStr for SignatureVerification {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disabled" => Ok(SignatureVerification::Disabled),
+            "record" => Ok(SignatureVerification::Record),
+            "reject" => Ok(SignatureVerification::Reject),
+            _ => Err(format!("Invalid signature verification mode {}", s)),
+        }
+    }
+}
+
+/// Raw fields of a data item that are covered by its signature.
+pub(crate) struct SignedFields<'a> {
+    pub signature_type: SignatureType,
+    pub signature: &'a [u8],
+    pub owner: &'a [u8],
+    pub target: Option<&'a [u8]>,
+    pub anchor: Option<&'a [u8]>,
+    pub tags: &'a [u8],
+    pub data: &'a [u8],
+}
+
+pub(crate) fn verify(fields: &SignedFields) -> SignatureStatus {
+    let message = signature_data(fields);
+    let valid = match fields.signature_type {
+        SignatureType::Arweave => verify_arweave(fields.owner, fields.signature, &message),
+        SignatureType::ED25519 | SignatureType::Solana => {
+            verify_ed25519(fields.owner, fields.signature, &message)
+        }
+        SignatureType::Ethereum => verify_ethereum(fields.owner, fields.signature, &message),
+        // Injected Aptos and typed Ethereum signatures cover a message wrapped
+        // by the wallet (Aptos message prefix and nonce, EIP-712 typed data)
+        // that ANS-104 does not specify
+        SignatureType::Injectedaptos
+        | SignatureType::Multiaptos
+        | SignatureType::Typedethereum
+        | SignatureType::Starknet => return SignatureStatus::Unsupported,
+    };
+    if valid {
+        SignatureStatus::Valid
+    } else {
+        SignatureStatus::Invalid
+    }
+}
+
+/// Message signed by the owner of a data item as defined by ANS-104.
+pub(crate) fn signature_data(fields: &SignedFields) -> [u8; DEEP_HASH_SIZE] {
+    let signature_type = u16::from(fields.signature_type).to_string();
+    deep_hash(&DeepHashChunk::List(vec![
+        DeepHashChunk::Blob(b"dataitem"),
+        DeepHashChunk::Blob(b"1"),
+        DeepHashChunk::Blob(signature_type.as_bytes()),
+        DeepHashChunk::Blob(fields.owner),
+        DeepHashChunk::Blob(fields.target.unwrap_or_default()),
+        DeepHashChunk::Blob(fields.anchor.unwrap_or_default()),
+        DeepHashChunk::Blob(fields.tags),
+        DeepHashChunk::Blob(fields.data),
+    ]))
+}
+
+fn verify_arweave(owner: &[u8], signature: &[u8], message: &[u8]) -> bool {
+    let Ok(key) = RsaPublicKey::new(BigUint::from_bytes_be(owner), BigUint::from(65537u32)) else {
+        return false;
+    };
+    let hashed = Sha256::digest(message);
+    // Signers use either a salt of the digest size (WebCrypto) or the maximum
+    // salt allowed by the key size (Node.js default), so both are accepted.
+    let digest_size = <Sha256 as Digest>::output_size();
+    let max_salt = rsa::traits::PublicKeyParts::size(&key).saturating_sub(digest_size + 2);
+    [digest_size, max_salt].into_iter().any(|salt| {
+        key.verify(Pss::new_with_salt::<Sha256>(salt), &hashed, signature)
+            .is_ok()
+    })
+}
+
+fn verify_ed25519(owner: &[u8], signature: &[u8], message: &[u8]) -> bool {
+    let Ok(owner) = owner.try_into() else {
+        return false;
+    };
+    let Ok(key) = ed25519_dalek::VerifyingKey::from_bytes(owner) else {
+        return false;
+    };
+    let Ok(signature) = ed25519_dalek::Signature::from_slice(signature) else {
+        return false;
+    };
+    key.verify(message, &signature).is_ok()
+}
+
+fn verify_ethereum(owner: &[u8], signature: &[u8], message: &[u8]) -> bool {
+    let Ok(key) = k256::ecdsa::VerifyingKey::from_sec1_bytes(owner) else {
+        return false;
+    };
+    // Signature is r || s || v, the recovery id is not needed with the key at hand
+    let Some(Ok(signature)) = signature.get(..64).map(k256::ecdsa::Signature::from_slice) else {
+        return false;
+    };
+    let signature = signature.normalize_s().unwrap_or(signature);
+    // Ethereum signers sign the message prefixed as defined by EIP-191
+    let digest = Keccak256::new()
+        .chain_update(format!("\x19Ethereum Signed Message:\n{}", message.len()))
+        .chain_update(message);
+    key.verify_digest(digest, &signature).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::Signer;
+    use k256::ecdsa::signature::DigestSigner;
+    use rsa::pkcs8::DecodePrivateKey;
+    use rsa::traits::PublicKeyParts;
+    use rsa::RsaPrivateKey;
+
+    fn fields<'a>(
+        signature_type: SignatureType,
+        signature: &'a [u8],
+        owner: &'a [u8],
+    ) -> SignedFields<'a> {
+        SignedFields {
+            signature_type,
+            signature,
+            owner,
+            target: Some(&[1u8; 32]),
+            anchor: None,
+            tags: b"\x02\x08Type\x08test\x00",
+            data: b"hello world",
+        }
+    }
+
+    #[test]
+    fn test_verify_arweave() {
+        // 4096 bit keys are too slow to generate in each run
+        let pem = std::fs::read_to_string("tests/keys/arweave-test-only.pem").unwrap();
+        let key = RsaPrivateKey::from_pkcs8_pem(&pem).unwrap();
+        let owner = key.n().to_bytes_be();
+        let message = signature_data(&fields(SignatureType::Arweave, &[], &owner));
+        let hashed = Sha256::digest(message);
+
+        for salt in [32, 478] {
+            let signature = key
+                .sign_with_rng(
+                    &mut rand::thread_rng(),
+                    Pss::new_with_salt::<Sha256>(salt),
+                    &hashed,
+                )
+                .unwrap();
+            let status = verify(&fields(SignatureType::Arweave, &signature, &owner));
+            assert_eq!(status, SignatureStatus::Valid);
+
+            let mut tampered = fields(SignatureType::Arweave, &signature, &owner);
+            tampered.data = b"hello world!";
+            assert_eq!(verify(&tampered), SignatureStatus::Invalid);
+        }
+    }
+
+    #[test]
+    fn test_verify_ed25519() {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+        let owner = key.verifying_key().to_bytes();
+
+        for signature_type in [SignatureType::ED25519, SignatureType::Solana] {
+            let message = signature_data(&fields(signature_type, &[], &owner));
+            let signature = key.sign(&message).to_bytes();
+            let status = verify(&fields(signature_type, &signature, &owner));
+            assert_eq!(status, SignatureStatus::Valid);
+
+            let mut tampered = fields(signature_type, &signature, &owner);
+            tampered.target = None;
+            assert_eq!(verify(&tampered), SignatureStatus::Invalid);
+        }
+    }
+
+    #[test]
+    fn test_verify_ethereum() {
+        let key = k256::ecdsa::SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let owner = key.verifying_key().to_encoded_point(false);
+        let message = signature_data(&fields(SignatureType::Ethereum, &[], owner.as_bytes()));
+        let digest = Keccak256::new()
+            .chain_update(format!("\x19Ethereum Signed Message:\n{}", message.len()))
+            .chain_update(message);
+        let (signature, recovery_id) = key.sign_digest_recoverable(digest).unwrap();
+        let mut signature = signature.to_vec();
+        signature.push(27 + recovery_id.to_byte());
+
+        let status = verify(&fields(
+            SignatureType::Ethereum,
+            &signature,
+            owner.as_bytes(),
+        ));
+        assert_eq!(status, SignatureStatus::Valid);
+
+        let mut tampered = fields(SignatureType::Ethereum, &signature, owner.as_bytes());
+        tampered.tags = b"\x02\x08Type\x08fake\x00";
+        assert_eq!(verify(&tampered), SignatureStatus::Invalid);
+
+        // Signed without the EIP-191 prefix
+        let unprefixed: k256::ecdsa::Signature =
+            key.sign_digest(Keccak256::new().chain_update(message));
+        let status = verify(&fields(
+            SignatureType::Ethereum,
+            &unprefixed.to_vec(),
+            owner.as_bytes(),
+        ));
+        assert_eq!(status, SignatureStatus::Invalid);
+    }
+
+    #[test]
+    fn test_verify_unsupported() {
+        let status = verify(&fields(SignatureType::Starknet, &[0u8; 128], &[0u8; 33]));
+        assert_eq!(status, SignatureStatus::Unsupported);
+    }
+}